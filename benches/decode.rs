@@ -0,0 +1,40 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rwobj::instructions::decode;
+use std::hint::black_box;
+use std::time::Duration;
+
+const WORDS: usize = 4_000_000;
+
+/// A fixed pseudo-random image so every run decodes the same words.
+fn image() -> Vec<u32> {
+    let mut state = 0x2545_f491_u32;
+    (0..WORDS)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        })
+        .collect()
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let words = image();
+    let mut group = c.benchmark_group("decode");
+    group.sample_size(10);
+    group.measurement_time(Duration::from_secs(10));
+    group.throughput(Throughput::Elements(words.len() as u64));
+    group.bench_function("4M words", |b| {
+        b.iter(|| {
+            let mut decoded = 0usize;
+            for &word in &words {
+                decoded += decode(black_box(word)).is_ok() as usize;
+            }
+            decoded
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode);
+criterion_main!(benches);
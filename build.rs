@@ -0,0 +1,111 @@
+//! Generates `ISA_NAME`, `INSN_TABLE`, `GPR_NAME` and `SPR_NAME` from
+//! `isa/wramp.toml`.
+
+#[path = "src/isa_spec.rs"]
+mod isa_spec;
+
+use isa_spec::{Format, IsaSpec};
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+const ISA_FILE: &str = "isa/wramp.toml";
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", ISA_FILE);
+    println!("cargo:rerun-if-changed=src/isa_spec.rs");
+    let text = fs::read_to_string(ISA_FILE).unwrap_or_else(|err| panic!("{}: {}", ISA_FILE, err));
+    let code = isa_spec::parse(&text)
+        .and_then(|spec| generate(&spec))
+        .unwrap_or_else(|err| panic!("{}: {}", ISA_FILE, err));
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("isa.rs");
+    fs::write(out, code).unwrap();
+}
+
+fn generate(spec: &IsaSpec) -> Result<String, String> {
+    let mut code = String::new();
+    let name = spec.name.as_deref().ok_or("missing string name")?;
+    writeln!(code, "pub const ISA_NAME: &str = {:?};", name).unwrap();
+    for (key, names, constant) in [
+        ("gpr", &spec.gpr, "GPR_NAME"),
+        ("spr", &spec.spr, "SPR_NAME"),
+    ] {
+        let names: Vec<&str> = names
+            .iter()
+            .map(Option::as_deref)
+            .collect::<Option<_>>()
+            .ok_or_else(|| format!("registers.{} must name all 16 registers", key))?;
+        writeln!(code, "pub const {}: [&str; 16] = {:?};", constant, names).unwrap();
+    }
+
+    let mut entries = Vec::new();
+    for insn in &spec.instructions {
+        let descriptor = match insn.format {
+            Format::R => "RType",
+            Format::I => "IType",
+            Format::J => "JType",
+        };
+        let operands: Vec<&str> = insn
+            .operands
+            .iter()
+            .map(|name| operand_kind(name))
+            .collect();
+        let tags: Vec<String> = insn
+            .tags
+            .iter()
+            .map(|tag| format!("Cow::Borrowed({:?})", tag))
+            .collect();
+        entries.push(format!(
+            "InsnType::new({:?}, &[{}], {:#x}, {:#x}, InsnDescriptor::{}, &[{}]),",
+            insn.mnemonic,
+            operands.join(", "),
+            insn.opcode,
+            insn.func,
+            descriptor,
+            tags.join(", ")
+        ));
+    }
+    for directive in &spec.directives {
+        entries.push(format!(
+            "InsnType::new({:?}, &[], 0xfff, 0xfff, InsnDescriptor::DIRECTIVE, &[]),",
+            directive
+        ));
+    }
+
+    // The decoder indexes the table with a `u8` and reserves 255 for "no
+    // match" (`instructions::NO_MATCH`).
+    if entries.len() >= 255 {
+        return Err(format!(
+            "{} entries is more than the decoder can index",
+            entries.len()
+        ));
+    }
+    writeln!(
+        code,
+        "pub static INSN_TABLE: [InsnType; {}] = [",
+        entries.len()
+    )
+    .unwrap();
+    for entry in entries {
+        writeln!(code, "    {}", entry).unwrap();
+    }
+    writeln!(code, "];").unwrap();
+    Ok(code)
+}
+
+/// The `OperandKind` expression for a name from `OPERAND_NAMES`.
+fn operand_kind(name: &str) -> &'static str {
+    match name {
+        "gpr(rd)" => "Gpr(Rd)",
+        "gpr(rs)" => "Gpr(Rs)",
+        "gpr(rt)" => "Gpr(Rt)",
+        "spr(rd)" => "Spr(Rd)",
+        "spr(rs)" => "Spr(Rs)",
+        "imm16" => "Imm16",
+        "offset20" => "Offset20",
+        "absolute20" => "Absolute20",
+        "relative20" => "Relative20",
+        _ => unreachable!("isa_spec accepted unknown operand {:?}", name),
+    }
+}
@@ -0,0 +1,770 @@
+//! A two-pass assembler that turns WRAMP assembly source into an object
+//! file, replacing the legacy C `wasm`.
+//!
+//! The first pass lays out the segments and gives every label its offset
+//! within its segment. The second evaluates expressions, so they may refer
+//! to labels and `.equ` constants defined further down, encodes each
+//! instruction against the ISA's operand schema and records a relocation
+//! wherever an operand or a `.word` still refers to a label or an external
+//! symbol, so the output links the same way as an object built by the C
+//! tools.
+//!
+//! A `.space` size decides where everything after it lands, so it is
+//! evaluated in the first pass and may only use constants defined above it.
+//!
+//! Macros, `.include` and conditional assembly are handled as lines are
+//! read, before the first pass sees them; see `preprocess`.
+
+mod expr;
+mod lex;
+mod preprocess;
+
+use crate::instructions::{
+    encode, immediate, InsnDescriptor, InsnType, Instruction, Operand, OperandKind, Target,
+};
+use crate::isa::Isa;
+use crate::object::{
+    ObjectFile, ReferenceType, RelocEntry, SegmentType, SymbolTable, MAX_SEGMENT_SIZE,
+};
+use expr::{Expr, Value};
+use lex::Token;
+use preprocess::Macro;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// A problem with one line of the source.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsmError {
+    /// The file the line is in; `None` for the source given to `assemble`.
+    pub file: Option<PathBuf>,
+    /// 1-based. Inside a macro expansion, the line of the macro body.
+    pub line: usize,
+    pub message: String,
+    /// The macro calls the line was expanded from, innermost first.
+    pub expansions: Vec<Expansion>,
+}
+
+/// A macro call that lines of source were expanded from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Expansion {
+    pub name: String,
+    /// Where the call is.
+    pub file: Option<PathBuf>,
+    pub line: usize,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}",
+            location(self.file.as_deref(), self.line),
+            self.message
+        )?;
+        for expansion in &self.expansions {
+            write!(
+                f,
+                ", in macro `{}` called at {}",
+                expansion.name,
+                location(expansion.file.as_deref(), expansion.line)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+fn location(file: Option<&Path>, line: usize) -> String {
+    match file {
+        Some(file) => format!("{}:{}", file.display(), line),
+        None => format!("line {}", line),
+    }
+}
+
+/// Assembles `source` against `isa`. Every line is checked, so on failure
+/// all the errors found are returned in source order. `.include` paths are
+/// relative to the current directory.
+pub fn assemble(source: &str, isa: &Isa) -> Result<ObjectFile, Vec<AsmError>> {
+    let mut assembler = Assembler::new(isa);
+    assembler.read(None, source, &[]);
+    assembler.emit()
+}
+
+/// Assembles `source`, which was read from `path`. Errors name the file,
+/// and `.include` paths are relative to its directory.
+pub fn assemble_named(path: &Path, source: &str, isa: &Isa) -> Result<ObjectFile, Vec<AsmError>> {
+    let mut assembler = Assembler::new(isa);
+    assembler.read(Some(path.to_path_buf()), source, &[]);
+    assembler.emit()
+}
+
+/// Where a line being assembled came from.
+#[derive(Clone, Debug)]
+struct Origin {
+    /// Counts lines in the order they were read, macro expansions and
+    /// included files included, so that errors can be sorted into it.
+    order: usize,
+    file: Option<PathBuf>,
+    line: usize,
+    expansions: Vec<Expansion>,
+}
+
+/// An operand as written, before its symbols are resolved.
+#[derive(Clone, Debug)]
+enum Arg {
+    Register(String),
+    Str(String),
+    Expr(Expr),
+    /// `expr($register)`; a bare `($register)` has a zero displacement.
+    Indexed(Expr, String),
+}
+
+#[derive(Clone, Debug)]
+enum Symbol {
+    /// Defined by `.equ`; evaluated where it is used.
+    Equ(Expr),
+    Label(SegmentType, u32),
+}
+
+/// Something the second pass emits, at the position the first pass gave
+/// it.
+struct Statement<'a> {
+    origin: Origin,
+    segment: SegmentType,
+    offset: u32,
+    body: Body<'a>,
+}
+
+enum Body<'a> {
+    /// A label definition, kept so that global labels are recorded in
+    /// source order.
+    Label(String),
+    Instruction(&'a InsnType, Vec<Arg>),
+    Words(Vec<Arg>),
+    /// Words known in the first pass: strings and zero-filled space.
+    Literal(Vec<u32>),
+    /// The name an `.equ` defines, evaluated once so that its errors are
+    /// reported even if it is never used.
+    Equ(String),
+}
+
+struct Assembler<'a> {
+    isa: &'a Isa,
+    symbols: HashMap<String, Symbol>,
+    /// `.global` and `.extern` names with the line that declared them.
+    globals: Vec<(Origin, String)>,
+    externs: Vec<(Origin, String)>,
+    macros: HashMap<String, Rc<Macro>>,
+    statements: Vec<Statement<'a>>,
+    segment: SegmentType,
+    /// Words laid out so far in text, data and bss.
+    sizes: [u32; 3],
+    /// Lines read so far, for `Origin::order`.
+    lines_read: usize,
+    /// Macro expansions so far, for `\@`.
+    expansion_count: usize,
+    /// Files being read, outermost first, to catch `.include` cycles.
+    includes: Vec<PathBuf>,
+    /// `.space` sizes the first pass could not evaluate.
+    unsized_spaces: Vec<(Origin, Expr)>,
+    /// Each with the `Origin::order` of its line.
+    errors: Vec<(usize, AsmError)>,
+}
+
+impl<'a> Assembler<'a> {
+    fn new(isa: &'a Isa) -> Self {
+        Self {
+            isa,
+            symbols: HashMap::new(),
+            globals: Vec::new(),
+            externs: Vec::new(),
+            macros: HashMap::new(),
+            statements: Vec::new(),
+            segment: SegmentType::Text,
+            sizes: [0; 3],
+            lines_read: 0,
+            expansion_count: 0,
+            includes: Vec::new(),
+            unsized_spaces: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    fn error(&mut self, origin: &Origin, message: String) {
+        self.errors.push((
+            origin.order,
+            AsmError {
+                file: origin.file.clone(),
+                line: origin.line,
+                message,
+                expansions: origin.expansions.clone(),
+            },
+        ));
+    }
+
+    /// First pass over one tokenized line: defines its labels and reserves
+    /// space for whatever it emits.
+    fn layout(&mut self, origin: &Origin, mut tokens: &[Token]) -> Result<(), String> {
+        while let [Token::Ident(name), Token::Punct(':'), rest @ ..] = tokens {
+            let offset = self.sizes[segment_index(self.segment)];
+            self.define(name, Symbol::Label(self.segment, offset))?;
+            self.push(origin, 0, Body::Label(name.clone()))?;
+            tokens = rest;
+        }
+        let (name, args) = match tokens {
+            [] => return Ok(()),
+            [Token::Ident(name), rest @ ..] => (name.as_str(), split_args(rest)?),
+            _ => return Err("expected a label, instruction or directive".to_string()),
+        };
+
+        match name {
+            ".text" | ".data" | ".bss" => {
+                no_args(name, &args)?;
+                self.segment = match name {
+                    ".text" => SegmentType::Text,
+                    ".data" => SegmentType::Data,
+                    _ => SegmentType::Bss,
+                };
+            }
+            ".global" | ".extern" => {
+                if args.is_empty() {
+                    return Err(format!("{} needs at least one symbol", name));
+                }
+                for arg in &args {
+                    let symbol = symbol_name(arg)?.to_string();
+                    match name {
+                        ".global" => self.globals.push((origin.clone(), symbol)),
+                        _ => self.externs.push((origin.clone(), symbol)),
+                    }
+                }
+            }
+            ".equ" => match &args[..] {
+                [name, value] => {
+                    let name = symbol_name(name)?;
+                    self.define(name, Symbol::Equ(expr(value)?.clone()))?;
+                    self.push(origin, 0, Body::Equ(name.to_string()))?;
+                }
+                _ => return Err(".equ takes a name and a value".to_string()),
+            },
+            ".word" => {
+                self.not_in_bss(name)?;
+                let size = args.len() as u32;
+                self.push(origin, size, Body::Words(args))?;
+            }
+            ".ascii" | ".asciiz" => {
+                self.not_in_bss(name)?;
+                let mut words = Vec::new();
+                for arg in &args {
+                    match arg {
+                        Arg::Str(string) => {
+                            words.extend(string.chars().map(|ch| ch as u32));
+                            if name == ".asciiz" {
+                                words.push(0);
+                            }
+                        }
+                        _ => return Err(format!("{} takes strings", name)),
+                    }
+                }
+                self.push(origin, words.len() as u32, Body::Literal(words))?;
+            }
+            ".space" => {
+                let size = match &args[..] {
+                    [size] => expr(size)?,
+                    _ => return Err(".space takes a size in words".to_string()),
+                };
+                let size = match self.constant(size) {
+                    Ok(size) => size,
+                    Err(_) => {
+                        self.unsized_spaces.push((origin.clone(), size.clone()));
+                        return Ok(());
+                    }
+                };
+                let size = u32::try_from(size).map_err(|_| format!("bad .space size {}", size))?;
+                let end = self.end_after(size)?;
+                if self.segment == SegmentType::Bss {
+                    self.sizes[segment_index(SegmentType::Bss)] = end;
+                } else {
+                    self.push(origin, size, Body::Literal(vec![0; size as usize]))?;
+                }
+            }
+            // Debugger information the linker does not use.
+            ".frame" | ".mask" => {}
+            _ => match self.isa.find(name) {
+                Some(insn) if *insn.type_descriptor() != InsnDescriptor::DIRECTIVE => {
+                    if self.segment != SegmentType::Text {
+                        return Err(format!("instruction `{}` outside the text segment", name));
+                    }
+                    self.push(origin, 1, Body::Instruction(insn, args))?;
+                }
+                Some(_) => return Err(format!("directive {} is not supported", name)),
+                None if name.starts_with('.') => return Err(format!("unknown directive {}", name)),
+                None => return Err(format!("unknown instruction `{}`", name)),
+            },
+        }
+        Ok(())
+    }
+
+    /// Records `body` at the current position and advances it by `size`
+    /// words.
+    fn push(&mut self, origin: &Origin, size: u32, body: Body<'a>) -> Result<(), String> {
+        let end = self.end_after(size)?;
+        let index = segment_index(self.segment);
+        self.statements.push(Statement {
+            origin: origin.clone(),
+            segment: self.segment,
+            offset: self.sizes[index],
+            body,
+        });
+        self.sizes[index] = end;
+        Ok(())
+    }
+
+    /// The size of the current segment once `size` more words are added,
+    /// which must still fit in the 20-bit address space.
+    fn end_after(&self, size: u32) -> Result<u32, String> {
+        self.sizes[segment_index(self.segment)]
+            .checked_add(size)
+            .filter(|&end| end <= MAX_SEGMENT_SIZE)
+            .ok_or_else(|| {
+                format!(
+                    "the {} segment would be larger than {:#x} words",
+                    self.segment.name().to_lowercase(),
+                    MAX_SEGMENT_SIZE
+                )
+            })
+    }
+
+    fn define(&mut self, name: &str, symbol: Symbol) -> Result<(), String> {
+        if self.symbols.contains_key(name) {
+            return Err(format!("`{}` is already defined", name));
+        }
+        self.symbols.insert(name.to_string(), symbol);
+        Ok(())
+    }
+
+    fn not_in_bss(&self, directive: &str) -> Result<(), String> {
+        if self.segment == SegmentType::Bss {
+            Err(format!(
+                "{} in the bss segment, which only takes .space",
+                directive
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Second pass: encodes every statement and builds the object file.
+    fn emit(mut self) -> Result<ObjectFile, Vec<AsmError>> {
+        self.check_declarations();
+        self.check_space_sizes();
+        let globals: HashSet<String> = self.globals.iter().map(|(_, name)| name.clone()).collect();
+        let mut output = Output::default();
+        let statements = std::mem::take(&mut self.statements);
+        for statement in &statements {
+            let result = match &statement.body {
+                Body::Label(name) => {
+                    if globals.contains(name) {
+                        output.global(statement, name);
+                    }
+                    Ok(())
+                }
+                Body::Instruction(insn, args) => {
+                    let word = self
+                        .instruction(&mut output, statement.offset, insn, args)
+                        .map_err(|err| format!("{}: {}", insn.mnemonic().unwrap_or("?"), err));
+                    output
+                        .words(statement.segment)
+                        .push(*word.as_ref().unwrap_or(&0));
+                    word.map(|_| ())
+                }
+                Body::Words(args) => {
+                    let mut result = Ok(());
+                    for (address, arg) in (statement.offset..).zip(args) {
+                        let word = self.word(&mut output, statement.segment, address, arg);
+                        output
+                            .words(statement.segment)
+                            .push(*word.as_ref().unwrap_or(&0));
+                        result = result.and(word.map(|_| ()));
+                    }
+                    result
+                }
+                Body::Literal(words) => {
+                    output.words(statement.segment).extend(words);
+                    Ok(())
+                }
+                Body::Equ(name) => self.evaluate(&Expr::Symbol(name.clone())).map(|_| ()),
+            };
+            if let Err(message) = result {
+                self.error(&statement.origin, message);
+            }
+        }
+
+        if !self.errors.is_empty() {
+            self.errors.sort_by_key(|&(order, _)| order);
+            return Err(self.errors.into_iter().map(|(_, err)| err).collect());
+        }
+        Ok(ObjectFile::from_parts(
+            output.text,
+            output.data,
+            self.sizes[segment_index(SegmentType::Bss)],
+            output.relocs,
+            output.symbol_table,
+        ))
+    }
+
+    /// Every `.global` must name a label defined here and every `.extern`
+    /// one that is not.
+    fn check_declarations(&mut self) {
+        let mut problems = Vec::new();
+        for (origin, name) in &self.globals {
+            match self.symbols.get(name) {
+                Some(Symbol::Label(..)) => {}
+                Some(Symbol::Equ(_)) => {
+                    problems.push((origin.clone(), format!("global `{}` is not a label", name)))
+                }
+                None => problems.push((
+                    origin.clone(),
+                    format!("global `{}` is never defined", name),
+                )),
+            }
+        }
+        for (origin, name) in &self.externs {
+            if self.symbols.contains_key(name) {
+                problems.push((
+                    origin.clone(),
+                    format!("external `{}` is defined here", name),
+                ));
+            }
+        }
+        for (origin, message) in problems {
+            self.error(&origin, message);
+        }
+    }
+
+    /// Reports the `.space` sizes the first pass could not evaluate. The
+    /// layout depends on them, so a size that only refers to constants
+    /// defined further down is an error too, but says so.
+    fn check_space_sizes(&mut self) {
+        for (origin, size) in std::mem::take(&mut self.unsized_spaces) {
+            let message = match self.constant(&size) {
+                Ok(_) => ".space size must be defined before the .space".to_string(),
+                Err(err) => err,
+            };
+            self.error(&origin, message);
+        }
+    }
+
+    /// Encodes one instruction at `address` in the text segment.
+    fn instruction(
+        &self,
+        output: &mut Output,
+        address: u32,
+        insn: &InsnType,
+        args: &[Arg],
+    ) -> Result<u32, String> {
+        let kinds = insn.operands();
+        // A displacement and its base register are written as one operand.
+        let expected = kinds.len()
+            - kinds
+                .iter()
+                .filter(|&&kind| kind == OperandKind::Offset20)
+                .count();
+        if args.len() != expected {
+            return Err(format!(
+                "expected {} operands, found {}",
+                expected,
+                args.len()
+            ));
+        }
+
+        let mut operands = Vec::new();
+        let mut kinds = kinds.iter();
+        for arg in args {
+            let kind = *kinds.next().unwrap();
+            let operand = match kind {
+                OperandKind::Gpr(_) => Operand::Gpr(self.gpr(arg)?),
+                OperandKind::Spr(_) => Operand::Spr(self.spr(arg)?),
+                OperandKind::Imm16 => match self.evaluate(expr(arg)?)? {
+                    Value::Absolute(value) => {
+                        Operand::Imm(immediate(value).map_err(|err| err.to_string())?)
+                    }
+                    _ => {
+                        return Err(
+                            "a 16-bit immediate cannot be relocated; use la for addresses"
+                                .to_string(),
+                        )
+                    }
+                },
+                OperandKind::Offset20 => {
+                    let (displacement, base) = match arg {
+                        Arg::Indexed(displacement, base) => (displacement, base),
+                        _ => return Err("expected `offset($register)`".to_string()),
+                    };
+                    let value = self.evaluate(displacement)?;
+                    let field = output.relocate(SegmentType::Text, address, value);
+                    operands.push(Operand::Offset(field_20(field)?));
+                    kinds.next();
+                    Operand::Gpr(self.gpr(&Arg::Register(base.clone()))?)
+                }
+                OperandKind::Absolute20 => {
+                    let value = self.evaluate(expr(arg)?)?;
+                    let field = output.relocate(SegmentType::Text, address, value);
+                    let target = u32::try_from(field)
+                        .map_err(|_| format!("address {} is negative", field))?;
+                    Operand::Target(Target::Absolute(target))
+                }
+                OperandKind::Relative20 => {
+                    let target = match self.evaluate(expr(arg)?)? {
+                        Value::Absolute(target) => target,
+                        Value::Label(SegmentType::Text, offset) => offset,
+                        Value::Label(..) => {
+                            return Err("branch target is not in the text segment".to_string())
+                        }
+                        Value::External(name, _) => {
+                            return Err(format!("cannot branch to external `{}`", name))
+                        }
+                    };
+                    let offset = field_20(target - (address as i64 + 1))?;
+                    Operand::Target(Target::Relative(offset))
+                }
+            };
+            operands.push(operand);
+        }
+        encode(&Instruction::new(insn, &operands)).map_err(|err| err.to_string())
+    }
+
+    /// One `.word` operand at `address` in `segment`.
+    fn word(
+        &self,
+        output: &mut Output,
+        segment: SegmentType,
+        address: u32,
+        arg: &Arg,
+    ) -> Result<u32, String> {
+        let value = self.evaluate(expr(arg)?)?;
+        let word = output.relocate(segment, address, value);
+        if (i32::MIN as i64..=u32::MAX as i64).contains(&word) {
+            Ok(word as u32)
+        } else {
+            Err(format!("{} does not fit in a word", word))
+        }
+    }
+
+    fn gpr(&self, arg: &Arg) -> Result<u8, String> {
+        self.register(arg, false)
+    }
+
+    fn spr(&self, arg: &Arg) -> Result<u8, String> {
+        self.register(arg, true)
+    }
+
+    /// Looks a register up by the ISA's name for it or by number, so that
+    /// `$sp` and `$14`, or `$estat` and `$spr5`, are the same register.
+    fn register(&self, arg: &Arg, special: bool) -> Result<u8, String> {
+        let (prefix, kind) = if special {
+            ("$spr", "special")
+        } else {
+            ("$", "general purpose")
+        };
+        let written = match arg {
+            Arg::Register(written) => written,
+            other => {
+                return Err(format!(
+                    "expected a {} register, found {}",
+                    kind,
+                    describe(other)
+                ))
+            }
+        };
+        let name = |register| match special {
+            true => self.isa.spr_name(register),
+            false => self.isa.gpr_name(register),
+        };
+        (0..16)
+            .find(|&register| name(register) == written)
+            .or_else(|| {
+                written
+                    .strip_prefix(prefix)
+                    .and_then(|number| number.parse().ok())
+                    .filter(|&number| number < 16)
+            })
+            .ok_or_else(|| format!("`{}` is not a {} register", written, kind))
+    }
+
+    /// Evaluates an expression that must not refer to a label, such as a
+    /// `.space` size.
+    fn constant(&self, expr: &Expr) -> Result<i64, String> {
+        match self.evaluate(expr)? {
+            Value::Absolute(value) => Ok(value),
+            _ => Err("expected a constant, not a label".to_string()),
+        }
+    }
+
+    fn evaluate(&self, expr: &Expr) -> Result<Value, String> {
+        self.evaluate_within(expr, &mut Vec::new())
+    }
+
+    /// Evaluates `expr` on behalf of the `.equ` definitions in `pending`,
+    /// which are still being evaluated, so that a definition that depends
+    /// on itself is reported instead of recursing forever.
+    fn evaluate_within(&self, expr: &Expr, pending: &mut Vec<String>) -> Result<Value, String> {
+        expr.evaluate(&mut |name| match self.symbols.get(name) {
+            Some(Symbol::Equ(value)) => {
+                if pending.iter().any(|pending| pending == name) {
+                    return Err(format!("`{}` is defined in terms of itself", name));
+                }
+                pending.push(name.to_string());
+                let value = self.evaluate_within(value, pending);
+                pending.pop();
+                value
+            }
+            Some(&Symbol::Label(segment, offset)) => Ok(Value::Label(segment, offset as i64)),
+            None if self.externs.iter().any(|(_, external)| external == name) => {
+                Ok(Value::External(name.to_string(), 0))
+            }
+            None => Err(format!("undefined symbol `{}`", name)),
+        })
+    }
+}
+
+/// The segments and relocations built by the second pass.
+#[derive(Default)]
+struct Output {
+    text: Vec<u32>,
+    data: Vec<u32>,
+    relocs: Vec<RelocEntry>,
+    symbol_table: SymbolTable,
+}
+
+impl Output {
+    fn words(&mut self, segment: SegmentType) -> &mut Vec<u32> {
+        match segment {
+            SegmentType::Data => &mut self.data,
+            _ => &mut self.text,
+        }
+    }
+
+    /// Records that the label defined by `statement` is global.
+    fn global(&mut self, statement: &Statement, name: &str) {
+        let ref_type = match statement.segment {
+            SegmentType::Data => ReferenceType::GlobalData,
+            SegmentType::Bss => ReferenceType::GlobalBss,
+            _ => ReferenceType::GlobalText,
+        };
+        self.relocs.push(RelocEntry {
+            address: statement.offset,
+            symbol_ptr: self.symbol_table.intern(name),
+            ref_type,
+            seg_type: None,
+            symbol_name: None,
+        });
+    }
+
+    /// The field value for `value` used by the word at `address` in
+    /// `segment`, recording a relocation if it refers to a label or an
+    /// external symbol. A label reference holds the offset within the
+    /// label's segment and an external reference holds the addend; the
+    /// linker adds the final address.
+    fn relocate(&mut self, segment: SegmentType, address: u32, value: Value) -> i64 {
+        let (ref_type, symbol_ptr, field) = match value {
+            Value::Absolute(value) => return value,
+            Value::Label(target, offset) => {
+                let ref_type = match target {
+                    SegmentType::Data => ReferenceType::DataLabelRef,
+                    SegmentType::Bss => ReferenceType::BssLabelRef,
+                    _ => ReferenceType::TextLabelRef,
+                };
+                (ref_type, 0, offset)
+            }
+            Value::External(name, addend) => (
+                ReferenceType::ExternalRef,
+                self.symbol_table.intern(&name),
+                addend,
+            ),
+        };
+        self.relocs.push(RelocEntry {
+            address,
+            symbol_ptr,
+            ref_type,
+            seg_type: Some(segment),
+            symbol_name: None,
+        });
+        field
+    }
+}
+
+fn segment_index(segment: SegmentType) -> usize {
+    match segment {
+        SegmentType::Data => 1,
+        SegmentType::Bss => 2,
+        _ => 0,
+    }
+}
+
+/// Narrows a displacement or branch offset; `encode` checks that it fits
+/// in 20 bits.
+fn field_20(value: i64) -> Result<i32, String> {
+    i32::try_from(value).map_err(|_| format!("offset {} does not fit in 20 bits", value))
+}
+
+/// Splits operand tokens at commas.
+fn split_args(tokens: &[Token]) -> Result<Vec<Arg>, String> {
+    if tokens.is_empty() {
+        return Ok(Vec::new());
+    }
+    tokens
+        .split(|token| *token == Token::Punct(','))
+        .map(parse_arg)
+        .collect()
+}
+
+fn parse_arg(tokens: &[Token]) -> Result<Arg, String> {
+    Ok(match tokens {
+        [] => return Err("missing operand".to_string()),
+        [Token::Register(name)] => Arg::Register(name.clone()),
+        [Token::Str(string)] => Arg::Str(string.clone()),
+        [displacement @ .., Token::Punct('('), Token::Register(base), Token::Punct(')')] => {
+            let displacement = match displacement {
+                [] => Expr::Number(0),
+                _ => expr::parse(displacement)?,
+            };
+            Arg::Indexed(displacement, base.clone())
+        }
+        _ => Arg::Expr(expr::parse(tokens)?),
+    })
+}
+
+fn expr(arg: &Arg) -> Result<&Expr, String> {
+    match arg {
+        Arg::Expr(expr) => Ok(expr),
+        other => Err(format!("expected a value, found {}", describe(other))),
+    }
+}
+
+fn symbol_name(arg: &Arg) -> Result<&str, String> {
+    match arg {
+        Arg::Expr(Expr::Symbol(name)) => Ok(name),
+        other => Err(format!("expected a symbol name, found {}", describe(other))),
+    }
+}
+
+fn no_args(directive: &str, args: &[Arg]) -> Result<(), String> {
+    if args.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} takes no operands", directive))
+    }
+}
+
+/// A short description of an operand for error messages.
+fn describe(arg: &Arg) -> String {
+    match arg {
+        Arg::Register(name) => format!("register `{}`", name),
+        Arg::Str(_) => "a string".to_string(),
+        Arg::Expr(_) => "a value".to_string(),
+        Arg::Indexed(_, base) => format!("an offset from `{}`", base),
+    }
+}
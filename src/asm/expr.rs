@@ -0,0 +1,321 @@
+//! Assembler expressions: parsing, and evaluation to a constant or to a
+//! value the linker still has to relocate.
+
+use super::lex::Token;
+use crate::object::SegmentType;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Expr {
+    Number(i64),
+    Symbol(String),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum UnaryOp {
+    Neg,
+    Not,
+    /// `%hi(x)`: bits 16-31, for `lhi`.
+    Hi,
+    /// `%lo(x)`: bits 0-15, for the `ori` that follows an `lhi`. `ori`
+    /// zero-extends, so unlike MIPS no carry into the high half is needed.
+    Lo,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Shl,
+    Shr,
+    And,
+    Or,
+    Xor,
+}
+
+impl BinaryOp {
+    /// Binding strength, as in C: `|` loosest, then `^`, `&`, shifts,
+    /// `+ -` and `* / %`.
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::Xor => 2,
+            BinaryOp::And => 3,
+            BinaryOp::Shl | BinaryOp::Shr => 4,
+            BinaryOp::Add | BinaryOp::Sub => 5,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 6,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
+            BinaryOp::And => "&",
+            BinaryOp::Or => "|",
+            BinaryOp::Xor => "^",
+        }
+    }
+}
+
+/// What an expression evaluates to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Value {
+    Absolute(i64),
+    /// An offset from the start of a segment: a label, plus or minus a
+    /// constant.
+    Label(SegmentType, i64),
+    /// An external symbol plus a constant addend.
+    External(String, i64),
+}
+
+pub(crate) fn parse(tokens: &[Token]) -> Result<Expr, String> {
+    let mut parser = Parser { tokens, next: 0 };
+    let expr = parser.binary(0)?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(token) => Err(format!("unexpected {} in expression", describe(token))),
+    }
+}
+
+impl Expr {
+    /// Evaluates the expression, looking symbols up with `resolve`.
+    pub(crate) fn evaluate(
+        &self,
+        resolve: &mut dyn FnMut(&str) -> Result<Value, String>,
+    ) -> Result<Value, String> {
+        match self {
+            Expr::Number(value) => Ok(Value::Absolute(*value)),
+            Expr::Symbol(name) => resolve(name),
+            Expr::Unary(op, operand) => {
+                let value = match operand.evaluate(resolve)? {
+                    Value::Absolute(value) => value,
+                    _ => {
+                        let name = match op {
+                            UnaryOp::Neg => "-",
+                            UnaryOp::Not => "~",
+                            UnaryOp::Hi => "%hi",
+                            UnaryOp::Lo => "%lo",
+                        };
+                        return Err(format!(
+                            "`{}` of a relocatable value cannot be relocated",
+                            name
+                        ));
+                    }
+                };
+                Ok(Value::Absolute(match op {
+                    UnaryOp::Neg => value.checked_neg().ok_or_else(overflow)?,
+                    UnaryOp::Not => !value,
+                    UnaryOp::Hi => (value >> 16) & 0xffff,
+                    UnaryOp::Lo => value & 0xffff,
+                }))
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = lhs.evaluate(resolve)?;
+                let rhs = rhs.evaluate(resolve)?;
+                apply(*op, lhs, rhs)
+            }
+        }
+    }
+}
+
+/// Combines two values. Constants combine freely; a relocatable value may
+/// only be moved by a constant, and two labels in the same segment may be
+/// subtracted to give the distance between them.
+fn apply(op: BinaryOp, lhs: Value, rhs: Value) -> Result<Value, String> {
+    use Value::{Absolute, External, Label};
+    Ok(match (op, lhs, rhs) {
+        (_, Absolute(lhs), Absolute(rhs)) => Absolute(arithmetic(op, lhs, rhs)?),
+        (BinaryOp::Add, Label(segment, offset), Absolute(addend))
+        | (BinaryOp::Add, Absolute(addend), Label(segment, offset)) => {
+            Label(segment, arithmetic(op, offset, addend)?)
+        }
+        (BinaryOp::Sub, Label(segment, offset), Absolute(addend)) => {
+            Label(segment, arithmetic(op, offset, addend)?)
+        }
+        (BinaryOp::Add, External(name, offset), Absolute(addend))
+        | (BinaryOp::Add, Absolute(addend), External(name, offset)) => {
+            External(name, arithmetic(op, offset, addend)?)
+        }
+        (BinaryOp::Sub, External(name, offset), Absolute(addend)) => {
+            External(name, arithmetic(op, offset, addend)?)
+        }
+        (BinaryOp::Sub, Label(lhs_segment, lhs), Label(rhs_segment, rhs)) => {
+            if lhs_segment != rhs_segment {
+                return Err(format!(
+                    "cannot subtract a {} label from a {} label",
+                    rhs_segment.name().to_lowercase(),
+                    lhs_segment.name().to_lowercase()
+                ));
+            }
+            Absolute(arithmetic(op, lhs, rhs)?)
+        }
+        (op, _, _) => {
+            return Err(format!(
+                "`{}` of a relocatable value cannot be relocated",
+                op.symbol()
+            ))
+        }
+    })
+}
+
+fn arithmetic(op: BinaryOp, lhs: i64, rhs: i64) -> Result<i64, String> {
+    let shift = || {
+        u32::try_from(rhs)
+            .ok()
+            .filter(|&shift| shift < 64)
+            .ok_or_else(|| format!("bad shift amount {}", rhs))
+    };
+    match op {
+        BinaryOp::Add => lhs.checked_add(rhs).ok_or_else(overflow),
+        BinaryOp::Sub => lhs.checked_sub(rhs).ok_or_else(overflow),
+        BinaryOp::Mul => lhs.checked_mul(rhs).ok_or_else(overflow),
+        BinaryOp::Div | BinaryOp::Rem if rhs == 0 => Err("division by zero".to_string()),
+        BinaryOp::Div => lhs.checked_div(rhs).ok_or_else(overflow),
+        BinaryOp::Rem => lhs.checked_rem(rhs).ok_or_else(overflow),
+        BinaryOp::Shl => Ok(lhs << shift()?),
+        BinaryOp::Shr => Ok(lhs >> shift()?),
+        BinaryOp::And => Ok(lhs & rhs),
+        BinaryOp::Or => Ok(lhs | rhs),
+        BinaryOp::Xor => Ok(lhs ^ rhs),
+    }
+}
+
+fn overflow() -> String {
+    "arithmetic overflow".to_string()
+}
+
+struct Parser<'t> {
+    tokens: &'t [Token],
+    next: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn eat(&mut self, punct: char) -> bool {
+        let found = self.peek() == Some(&Token::Punct(punct));
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, punct: char) -> Result<(), String> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(match self.peek() {
+                Some(token) => format!("expected `{}`, found {}", punct, describe(token)),
+                None => format!("expected `{}`", punct),
+            })
+        }
+    }
+
+    /// The binary operator at the cursor and how many tokens it spans.
+    fn operator(&self) -> Option<(BinaryOp, usize)> {
+        let rest = &self.tokens[self.next..];
+        Some(match rest {
+            [Token::Punct('<'), Token::Punct('<'), ..] => (BinaryOp::Shl, 2),
+            [Token::Punct('>'), Token::Punct('>'), ..] => (BinaryOp::Shr, 2),
+            [Token::Punct(punct), ..] => {
+                let op = match punct {
+                    '+' => BinaryOp::Add,
+                    '-' => BinaryOp::Sub,
+                    '*' => BinaryOp::Mul,
+                    '/' => BinaryOp::Div,
+                    '%' => BinaryOp::Rem,
+                    '&' => BinaryOp::And,
+                    '|' => BinaryOp::Or,
+                    '^' => BinaryOp::Xor,
+                    _ => return None,
+                };
+                (op, 1)
+            }
+            _ => return None,
+        })
+    }
+
+    /// Parses operators that bind tighter than `min_precedence`,
+    /// left-associatively.
+    fn binary(&mut self, min_precedence: u8) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        while let Some((op, width)) = self.operator() {
+            if op.precedence() <= min_precedence {
+                break;
+            }
+            self.next += width;
+            let rhs = self.binary(op.precedence())?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        let unary = |op, operand| Ok(Expr::Unary(op, Box::new(operand)));
+        match self.peek().cloned() {
+            Some(Token::Punct('-')) => {
+                self.next += 1;
+                unary(UnaryOp::Neg, self.unary()?)
+            }
+            Some(Token::Punct('~')) => {
+                self.next += 1;
+                unary(UnaryOp::Not, self.unary()?)
+            }
+            Some(Token::Punct('+')) => {
+                self.next += 1;
+                self.unary()
+            }
+            Some(Token::Punct('%')) => {
+                self.next += 1;
+                let op = match self.peek() {
+                    Some(Token::Ident(name)) if name == "hi" => UnaryOp::Hi,
+                    Some(Token::Ident(name)) if name == "lo" => UnaryOp::Lo,
+                    _ => return Err("expected %hi or %lo".to_string()),
+                };
+                self.next += 1;
+                self.expect('(')?;
+                let operand = self.binary(0)?;
+                self.expect(')')?;
+                unary(op, operand)
+            }
+            Some(Token::Punct('(')) => {
+                self.next += 1;
+                let expr = self.binary(0)?;
+                self.expect(')')?;
+                Ok(expr)
+            }
+            Some(Token::Number(value)) => {
+                self.next += 1;
+                Ok(Expr::Number(value))
+            }
+            Some(Token::Ident(name)) => {
+                self.next += 1;
+                Ok(Expr::Symbol(name))
+            }
+            Some(token) => Err(format!("unexpected {} in expression", describe(&token))),
+            None => Err("missing value".to_string()),
+        }
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Ident(name) => format!("`{}`", name),
+        Token::Register(name) => format!("register `{}`", name),
+        Token::Number(value) => format!("`{}`", value),
+        Token::Str(_) => "a string".to_string(),
+        Token::Punct(punct) => format!("`{}`", punct),
+    }
+}
@@ -0,0 +1,141 @@
+//! Splits a line of assembly source into tokens.
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Token {
+    /// A mnemonic, directive or symbol name.
+    Ident(String),
+    /// A register name, including its `$`.
+    Register(String),
+    /// An integer or character literal.
+    Number(i64),
+    Str(String),
+    Punct(char),
+}
+
+/// Tokenizes one line, stopping at a `#` comment.
+pub(crate) fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.char_indices().peekable();
+    while let Some(&(start, ch)) = chars.peek() {
+        match ch {
+            '#' => break,
+            _ if ch.is_whitespace() => {
+                chars.next();
+            }
+            '$' => {
+                chars.next();
+                let name = take_while(line, start + 1, &mut chars, is_ident_char);
+                if name.is_empty() {
+                    return Err("`$` is not followed by a register name".to_string());
+                }
+                tokens.push(Token::Register(format!("${}", name)));
+            }
+            '"' => {
+                chars.next();
+                tokens.push(Token::Str(string_literal(&mut chars)?));
+            }
+            '\'' => {
+                chars.next();
+                let value = match chars.next() {
+                    Some((_, '\\')) => escape(&mut chars)?,
+                    Some((_, '\'')) | None => return Err("empty character literal".to_string()),
+                    Some((_, ch)) => ch,
+                };
+                if chars.next().map(|(_, ch)| ch) != Some('\'') {
+                    return Err("unterminated character literal".to_string());
+                }
+                tokens.push(Token::Number(value as i64));
+            }
+            _ if ch.is_ascii_digit() => {
+                let text = take_while(line, start, &mut chars, is_ident_char);
+                tokens.push(Token::Number(number(text)?));
+            }
+            _ if is_ident_start(ch) => {
+                let name = take_while(line, start, &mut chars, is_ident_char);
+                tokens.push(Token::Ident(name.to_string()));
+            }
+            _ => {
+                chars.next();
+                tokens.push(Token::Punct(ch));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn is_ident_start(ch: char) -> bool {
+    ch.is_ascii_alphabetic() || ch == '_' || ch == '.'
+}
+
+fn is_ident_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '_' || ch == '.'
+}
+
+/// Consumes characters from byte offset `start` while `accept` holds and
+/// returns them.
+fn take_while<'a>(
+    line: &'a str,
+    start: usize,
+    chars: &mut std::iter::Peekable<std::str::CharIndices>,
+    accept: fn(char) -> bool,
+) -> &'a str {
+    let mut end = start;
+    while let Some(&(index, ch)) = chars.peek() {
+        if !accept(ch) {
+            break;
+        }
+        end = index + ch.len_utf8();
+        chars.next();
+    }
+    &line[start..end]
+}
+
+/// Decimal, `0x` hexadecimal or `0b` binary.
+fn number(text: &str) -> Result<i64, String> {
+    let lower = text.to_ascii_lowercase();
+    let parsed = if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2)
+    } else {
+        lower.parse()
+    };
+    parsed.map_err(|_| format!("invalid number `{}`", text))
+}
+
+fn string_literal(
+    chars: &mut std::iter::Peekable<std::str::CharIndices>,
+) -> Result<String, String> {
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            Some((_, '"')) => return Ok(value),
+            Some((_, '\\')) => value.push(escape(chars)?),
+            Some((_, ch)) => value.push(ch),
+            None => return Err("unterminated string".to_string()),
+        }
+    }
+}
+
+/// The character a backslash escape stands for, after the backslash.
+fn escape(chars: &mut std::iter::Peekable<std::str::CharIndices>) -> Result<char, String> {
+    Ok(match chars.next().map(|(_, ch)| ch) {
+        Some('n') => '\n',
+        Some('t') => '\t',
+        Some('r') => '\r',
+        Some('0') => '\0',
+        Some('\\') => '\\',
+        Some('"') => '"',
+        Some('\'') => '\'',
+        Some('x') => {
+            let digits: String = (0..2)
+                .filter_map(|_| chars.next().map(|(_, ch)| ch))
+                .collect();
+            u8::from_str_radix(&digits, 16)
+                .map(char::from)
+                .map_err(|_| format!("invalid escape `\\x{}`", digits))?
+        }
+        Some(ch) => return Err(format!("unknown escape `\\{}`", ch)),
+        None => return Err("unterminated escape".to_string()),
+    })
+}
@@ -0,0 +1,403 @@
+//! Feeds source lines to the first pass, acting on the directives that
+//! decide which lines it sees: `.macro`/`.endm`, `.include` and
+//! `.if`/`.else`/`.endif`.
+//!
+//! A macro body is kept as text. Each call replaces `\name` with the
+//! argument for parameter `name` and `\@` with a number unique to the
+//! expansion, so that a label written `skip\@:` is distinct in every
+//! expansion, then reads the result as if it had been written at the call.
+//! Lines keep the file and line number they were defined at, and carry the
+//! chain of calls that expanded them for error messages.
+
+use super::lex::{self, Token};
+use super::{expr, Assembler, Expansion, Origin};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// How deeply macro calls and includes may nest, to stop runaway
+/// recursion.
+const MAX_DEPTH: usize = 64;
+
+/// A macro defined with `.macro`.
+pub(super) struct Macro {
+    params: Vec<String>,
+    file: Option<PathBuf>,
+    /// The lines between `.macro` and `.endm`, with their line numbers.
+    body: Vec<(usize, String)>,
+}
+
+/// A `.macro` whose body is still being read.
+struct Definition {
+    origin: Origin,
+    /// `None` for a definition that was rejected, whose body is read only
+    /// so that it is not assembled in place.
+    name: Option<String>,
+    params: Vec<String>,
+    body: Vec<(usize, String)>,
+}
+
+/// An `.if` whose `.endif` has not been reached.
+struct Conditional {
+    origin: Origin,
+    /// Whether the branch being read is assembled.
+    active: bool,
+    /// Whether a branch has been assembled already, or the whole block is
+    /// inside one that is not, so that `.else` must not be.
+    taken: bool,
+    seen_else: bool,
+}
+
+impl Assembler<'_> {
+    /// Reads the lines of `text`, from `file`, expanded from `expansions`.
+    pub(super) fn read(&mut self, file: Option<PathBuf>, text: &str, expansions: &[Expansion]) {
+        let lines: Vec<_> = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.to_string()))
+            .collect();
+        if let Some(file) = &file {
+            self.includes.push(file.clone());
+        }
+        let included = file.is_some();
+        self.read_lines(file, &lines, expansions);
+        if included {
+            self.includes.pop();
+        }
+    }
+
+    /// Reads a file or one macro expansion. A `.macro` or `.if` opened in
+    /// it must be closed in it too.
+    fn read_lines(
+        &mut self,
+        file: Option<PathBuf>,
+        lines: &[(usize, String)],
+        expansions: &[Expansion],
+    ) {
+        let mut definition: Option<Definition> = None;
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        for (line, text) in lines {
+            self.lines_read += 1;
+            let origin = Origin {
+                order: self.lines_read,
+                file: file.clone(),
+                line: *line,
+                expansions: expansions.to_vec(),
+            };
+            let tokens = lex::tokenize(text);
+            let directive = match tokens.as_deref() {
+                Ok([Token::Ident(name), ..]) => name.as_str(),
+                _ => "",
+            };
+
+            if let Some(open) = &mut definition {
+                match directive {
+                    ".endm" => {
+                        let open = definition.take().unwrap();
+                        self.define_macro(open, file.clone());
+                    }
+                    ".macro" => self.error(&origin, "a .macro cannot define another".to_string()),
+                    _ => open.body.push((*line, text.clone())),
+                }
+                continue;
+            }
+            if let ".if" | ".else" | ".endif" = directive {
+                let rest = &tokens.as_deref().unwrap_or_default()[1..];
+                if let Err(message) = self.conditional(&origin, directive, rest, &mut conditionals)
+                {
+                    self.error(&origin, message);
+                }
+                continue;
+            }
+            if !conditionals.iter().all(|conditional| conditional.active) {
+                continue;
+            }
+
+            let result = tokens.and_then(|tokens| self.line(&origin, &tokens, &mut definition));
+            if let Err(message) = result {
+                self.error(&origin, message);
+            }
+        }
+
+        if let Some(open) = definition {
+            self.error(&open.origin, ".macro has no .endm".to_string());
+        }
+        for conditional in conditionals {
+            self.error(&conditional.origin, ".if has no .endif".to_string());
+        }
+    }
+
+    /// One line outside any macro definition, in a branch being assembled.
+    fn line(
+        &mut self,
+        origin: &Origin,
+        tokens: &[Token],
+        definition: &mut Option<Definition>,
+    ) -> Result<(), String> {
+        let mut labels = 0;
+        while let [Token::Ident(_), Token::Punct(':'), ..] = tokens[labels..] {
+            labels += 2;
+        }
+        let (head, rest) = match &tokens[labels..] {
+            [Token::Ident(head), rest @ ..] => (head.as_str(), rest),
+            _ => return self.layout(origin, tokens),
+        };
+        match head {
+            ".macro" => {
+                self.layout(origin, &tokens[..labels])?;
+                let (open, result) = match self.open_macro(origin, rest) {
+                    Ok(open) => (open, Ok(())),
+                    Err(message) => {
+                        let rejected = Definition {
+                            origin: origin.clone(),
+                            name: None,
+                            params: Vec::new(),
+                            body: Vec::new(),
+                        };
+                        (rejected, Err(message))
+                    }
+                };
+                *definition = Some(open);
+                result
+            }
+            ".endm" => Err(".endm without .macro".to_string()),
+            ".if" | ".else" | ".endif" => Err(format!("{} cannot follow a label", head)),
+            ".include" => {
+                self.layout(origin, &tokens[..labels])?;
+                self.include(origin, rest)
+            }
+            _ => match self.macros.get(head).cloned() {
+                Some(called) => {
+                    self.layout(origin, &tokens[..labels])?;
+                    self.expand(origin, head, &called, rest)
+                }
+                None => self.layout(origin, tokens),
+            },
+        }
+    }
+
+    /// Starts a definition from the operands of `.macro`: a name, then
+    /// parameter names separated by commas.
+    fn open_macro(&self, origin: &Origin, tokens: &[Token]) -> Result<Definition, String> {
+        let (name, rest) = match tokens {
+            [Token::Ident(name), rest @ ..] => (name, rest),
+            _ => return Err(".macro needs a name".to_string()),
+        };
+        if name.starts_with('.') || self.isa.find(name).is_some() {
+            return Err(format!("cannot define a macro named `{}`", name));
+        }
+        let mut params: Vec<String> = Vec::new();
+        if !rest.is_empty() {
+            for param in rest.split(|token| *token == Token::Punct(',')) {
+                match param {
+                    [Token::Ident(param)] if !params.contains(param) => params.push(param.clone()),
+                    [Token::Ident(param)] => {
+                        return Err(format!("parameter `{}` is listed twice", param))
+                    }
+                    _ => return Err("expected a parameter name".to_string()),
+                }
+            }
+        }
+        Ok(Definition {
+            origin: origin.clone(),
+            name: Some(name.clone()),
+            params,
+            body: Vec::new(),
+        })
+    }
+
+    fn define_macro(&mut self, definition: Definition, file: Option<PathBuf>) {
+        let Some(name) = definition.name else {
+            return;
+        };
+        if self.macros.contains_key(&name) {
+            let message = format!("macro `{}` is already defined", name);
+            self.error(&definition.origin, message);
+            return;
+        }
+        let defined = Macro {
+            params: definition.params,
+            file,
+            body: definition.body,
+        };
+        self.macros.insert(name, Rc::new(defined));
+    }
+
+    /// Reads the body of `called` with the arguments in `tokens`
+    /// substituted.
+    fn expand(
+        &mut self,
+        origin: &Origin,
+        name: &str,
+        called: &Macro,
+        tokens: &[Token],
+    ) -> Result<(), String> {
+        if origin.expansions.len() >= MAX_DEPTH {
+            return Err(format!(
+                "macro `{}` nests more than {} calls deep",
+                name, MAX_DEPTH
+            ));
+        }
+        let args: Vec<String> = if tokens.is_empty() {
+            Vec::new()
+        } else {
+            tokens
+                .split(|token| *token == Token::Punct(','))
+                .map(render)
+                .collect()
+        };
+        if args.len() != called.params.len() {
+            return Err(format!(
+                "macro `{}` takes {} arguments, found {}",
+                name,
+                called.params.len(),
+                args.len()
+            ));
+        }
+
+        self.expansion_count += 1;
+        let body: Vec<_> = called
+            .body
+            .iter()
+            .map(|(line, text)| {
+                let text = substitute(text, &called.params, &args, self.expansion_count);
+                (*line, text)
+            })
+            .collect();
+        let mut expansions = vec![Expansion {
+            name: name.to_string(),
+            file: origin.file.clone(),
+            line: origin.line,
+        }];
+        expansions.extend(origin.expansions.iter().cloned());
+        self.read_lines(called.file.clone(), &body, &expansions);
+        Ok(())
+    }
+
+    /// Reads the file named by the operand of `.include`, relative to the
+    /// directory of the file that includes it.
+    fn include(&mut self, origin: &Origin, tokens: &[Token]) -> Result<(), String> {
+        let name = match tokens {
+            [Token::Str(name)] => name,
+            _ => return Err(".include takes a file name in quotes".to_string()),
+        };
+        let path = match &origin.file {
+            Some(file) => file.parent().unwrap_or(Path::new("")).join(name),
+            None => PathBuf::from(name),
+        };
+        if self.includes.contains(&path) || self.includes.len() >= MAX_DEPTH {
+            return Err(format!("{} includes itself", path.display()));
+        }
+        let text =
+            fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        self.read(Some(path), &text, &origin.expansions);
+        Ok(())
+    }
+
+    /// Acts on `.if`, `.else` or `.endif`. A condition that cannot be
+    /// evaluated is reported and neither branch is assembled.
+    fn conditional(
+        &self,
+        origin: &Origin,
+        directive: &str,
+        tokens: &[Token],
+        conditionals: &mut Vec<Conditional>,
+    ) -> Result<(), String> {
+        if directive == ".if" {
+            let enclosing = conditionals.iter().all(|conditional| conditional.active);
+            let value = match enclosing {
+                true => expr::parse(tokens).and_then(|condition| self.constant(&condition)),
+                false => Ok(0),
+            };
+            let holds = matches!(value, Ok(value) if value != 0);
+            conditionals.push(Conditional {
+                origin: origin.clone(),
+                active: enclosing && holds,
+                taken: !enclosing || holds || value.is_err(),
+                seen_else: false,
+            });
+            return value.map(|_| ());
+        }
+
+        if !tokens.is_empty() {
+            return Err(format!("{} takes no operands", directive));
+        }
+        if directive == ".endif" {
+            return match conditionals.pop() {
+                Some(_) => Ok(()),
+                None => Err(".endif without .if".to_string()),
+            };
+        }
+        let open = conditionals
+            .last_mut()
+            .ok_or_else(|| ".else without .if".to_string())?;
+        if open.seen_else {
+            return Err("a second .else for the same .if".to_string());
+        }
+        open.seen_else = true;
+        open.active = !open.taken;
+        open.taken = true;
+        Ok(())
+    }
+}
+
+/// Replaces `\param` with its argument and `\@` with `expansion` in one
+/// line of a macro body. Other backslashes, such as string escapes, are
+/// left alone.
+fn substitute(text: &str, params: &[String], args: &[String], expansion: usize) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(index) = rest.find('\\') {
+        result.push_str(&rest[..index]);
+        rest = &rest[index + 1..];
+        if let Some(after) = rest.strip_prefix('@') {
+            result.push_str(&expansion.to_string());
+            rest = after;
+            continue;
+        }
+        let len = rest
+            .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_'))
+            .unwrap_or(rest.len());
+        match params.iter().position(|param| *param == rest[..len]) {
+            Some(param) => {
+                result.push_str(&args[param]);
+                rest = &rest[len..];
+            }
+            None => result.push('\\'),
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Turns a macro argument back into source text.
+fn render(tokens: &[Token]) -> String {
+    let words: Vec<String> = tokens
+        .iter()
+        .map(|token| match token {
+            Token::Ident(name) | Token::Register(name) => name.clone(),
+            Token::Number(value) => value.to_string(),
+            Token::Str(string) => quote(string),
+            Token::Punct(punct) => punct.to_string(),
+        })
+        .collect();
+    words.join(" ")
+}
+
+fn quote(string: &str) -> String {
+    let mut quoted = String::from('"');
+    for ch in string.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            '\0' => quoted.push_str("\\0"),
+            _ if ch.is_ascii_control() => quoted.push_str(&format!("\\x{:02x}", ch as u32)),
+            _ => quoted.push(ch),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,55 @@
+use crate::error::ObjError;
+use crate::object::{ObjectFile, SegmentType};
+use std::collections::HashMap;
+
+impl ObjectFile {
+    /// Checks the invariants that `parse` does not enforce on its own and
+    /// returns every violation found.
+    ///
+    /// `parse` already rejects sizes that disagree with the file length,
+    /// unknown reference and segment codes, relocation addresses outside
+    /// their segment and symbol pointers outside the name table, so a loaded
+    /// object only needs the checks that look across entries.
+    pub fn check(&self) -> Vec<ObjError> {
+        let mut problems = Vec::new();
+        let name_starts: Vec<u32> = self.symbol_table.entries().map(|(ptr, _)| ptr).collect();
+        let mut globals: HashMap<&str, u32> = HashMap::new();
+
+        for (index, entry) in self.reloc_entries.iter().enumerate() {
+            let offset = self.reloc_entry_offset(index);
+            let index = index as u32;
+
+            if entry.ref_type.has_symbol() && !name_starts.contains(&entry.symbol_ptr) {
+                problems.push(ObjError::SymbolPointerMidName {
+                    index,
+                    offset: offset + 4,
+                    ptr: entry.symbol_ptr,
+                });
+            }
+
+            if !entry.ref_type.is_global()
+                && !matches!(entry.seg_type, Some(SegmentType::Text | SegmentType::Data))
+            {
+                problems.push(ObjError::BadRefSegment {
+                    index,
+                    offset: offset + 9,
+                    segment: entry.seg_type,
+                });
+            }
+
+            if let (true, Some(name)) = (entry.ref_type.is_global(), &entry.symbol_name) {
+                if let Some(&first_index) = globals.get(name.as_str()) {
+                    problems.push(ObjError::DuplicateGlobal {
+                        index,
+                        offset,
+                        name: name.clone(),
+                        first_index,
+                    });
+                } else {
+                    globals.insert(name, index);
+                }
+            }
+        }
+        problems
+    }
+}
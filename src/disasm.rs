@@ -0,0 +1,423 @@
+//! Object-aware disassembly: operands that the relocation table patches are
+//! printed as the symbols they refer to, and the data and bss segments are
+//! rendered as directives.
+
+use crate::format::Formatter;
+use crate::instructions::{DecodeError, Instruction, Operand, Target};
+use crate::isa::Isa;
+use crate::object::{ObjectFile, ReferenceType, RelocEntry, SegmentType};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io;
+use std::io::Write;
+
+/// The 20-bit field a relocation patches in a text word.
+const FIELD_MASK: u32 = 0xfffff;
+
+/// Indents instructions under their labels when the address and word
+/// columns are not shown.
+const INDENT: &str = "        ";
+
+/// The most values printed on one `.word` line.
+const WORDS_PER_DIRECTIVE: usize = 4;
+
+pub struct Disassembler<'a> {
+    object: &'a ObjectFile,
+    formatter: Formatter,
+    /// Relocations that patch a text or data word, by segment and offset.
+    relocs: HashMap<(SegmentType, u32), &'a RelocEntry>,
+    /// Offsets of the relocated data words, for breaking `.word` runs.
+    data_relocs: BTreeSet<u32>,
+    /// Global and synthesized labels by segment and offset. Several
+    /// globals may name the same offset; the first is used in operands.
+    labels: HashMap<SegmentType, BTreeMap<u32, Vec<String>>>,
+}
+
+/// The name given to a referenced address that has no symbol.
+pub fn local_label(address: u32) -> String {
+    format!("L_{:05x}", address)
+}
+
+impl<'a> Disassembler<'a> {
+    pub fn new(object: &'a ObjectFile, formatter: Formatter) -> Self {
+        let mut relocs = HashMap::new();
+        let mut labels: HashMap<SegmentType, BTreeMap<u32, Vec<String>>> = HashMap::new();
+        for entry in &object.reloc_entries {
+            if entry.ref_type.is_global() {
+                if let (Some(seg), Some(name)) =
+                    (entry.ref_type.target_segment(), &entry.symbol_name)
+                {
+                    let names = labels
+                        .entry(seg)
+                        .or_default()
+                        .entry(entry.address)
+                        .or_default();
+                    if !names.contains(name) {
+                        names.push(name.clone());
+                    }
+                }
+            } else if let Some(seg) = entry.seg_type {
+                relocs.insert((seg, entry.address), entry);
+            }
+        }
+        let data_relocs = relocs
+            .keys()
+            .filter(|(seg, _)| *seg == SegmentType::Data)
+            .map(|&(_, offset)| offset)
+            .collect();
+        let mut disassembler = Self {
+            object,
+            formatter,
+            relocs,
+            data_relocs,
+            labels,
+        };
+        disassembler.synthesize_labels();
+        disassembler
+    }
+
+    /// Gives every branch target inside the text segment, and every label
+    /// reference into any segment, a `local_label` unless a symbol already
+    /// names it.
+    ///
+    /// A jump or `la` without a relocation holds a fixed address rather
+    /// than a label, so it is left as a number.
+    fn synthesize_labels(&mut self) {
+        let text_start = self.object.segment_address(SegmentType::Text);
+        let mut targets = Vec::new();
+        for (offset, &word) in self.object.text.iter().enumerate() {
+            let offset = offset as u32;
+            if !self.relocs.contains_key(&(SegmentType::Text, offset)) {
+                let target = self
+                    .formatter
+                    .isa()
+                    .decode(word)
+                    .ok()
+                    .and_then(|insn| branch_target(&insn, text_start + offset))
+                    .and_then(|target| target.checked_sub(text_start));
+                targets.extend(target.map(|target| (SegmentType::Text, target, false)));
+            }
+        }
+        for (&(seg, offset), entry) in &self.relocs {
+            if let Some(target_seg) = entry.ref_type.target_segment() {
+                targets.push((target_seg, self.field(seg, offset), true));
+            }
+        }
+
+        for (seg, mut offset, relocated) in targets {
+            // A label may sit one past the end of its segment. A relocated
+            // reference beyond that is written relative to the segment's
+            // first label, so the segment needs one; a branch beyond it is
+            // printed as a number.
+            if offset > self.object.segment_size(seg) {
+                if !relocated
+                    || self
+                        .labels
+                        .get(&seg)
+                        .is_some_and(|labels| !labels.is_empty())
+                {
+                    continue;
+                }
+                offset = 0;
+            }
+            let address = self.object.segment_address(seg) + offset;
+            self.labels
+                .entry(seg)
+                .or_default()
+                .entry(offset)
+                .or_insert_with(|| vec![local_label(address)]);
+        }
+    }
+
+    /// The value of the field a relocation at `offset` in `seg` patches:
+    /// the 20-bit address field of an instruction, or a whole data word.
+    fn field(&self, seg: SegmentType, offset: u32) -> u32 {
+        match seg {
+            SegmentType::Text => self.object.text[offset as usize] & FIELD_MASK,
+            SegmentType::Data => self.object.data[offset as usize],
+            _ => 0,
+        }
+    }
+
+    /// The label defined at exactly `offset` within `seg`, if any.
+    pub fn label_at(&self, seg: SegmentType, offset: u32) -> Option<&str> {
+        self.labels_at(seg, offset).first().map(String::as_str)
+    }
+
+    /// Every label defined at exactly `offset` within `seg`.
+    fn labels_at(&self, seg: SegmentType, offset: u32) -> &[String] {
+        self.labels
+            .get(&seg)
+            .and_then(|labels| labels.get(&offset))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// A label line for each label at `offset` within `seg`.
+    fn write_labels<W: Write>(&self, out: &mut W, seg: SegmentType, offset: u32) -> io::Result<()> {
+        for label in self.labels_at(seg, offset) {
+            writeln!(out, "{}:", label)?;
+        }
+        Ok(())
+    }
+
+    /// Names `offset` within `seg` by the closest label at or before it, as
+    /// `name` or `name+k`.
+    pub fn label_for(&self, seg: SegmentType, offset: u32) -> Option<String> {
+        let (&start, names) = self.labels.get(&seg)?.range(..=offset).next_back()?;
+        let name = names.first()?;
+        Some(match offset - start {
+            0 => name.to_string(),
+            delta => format!("{}+{}", name, delta),
+        })
+    }
+
+    /// The symbol the relocated field at `offset` in `seg` refers to, and
+    /// whether it is an unresolved external.
+    fn reloc_symbol(&self, seg: SegmentType, offset: u32) -> Option<(String, bool)> {
+        let entry = self.relocs.get(&(seg, offset))?;
+        let field = self.field(seg, offset);
+        match (entry.ref_type, &entry.symbol_name) {
+            (ReferenceType::ExternalRef, Some(name)) if field == 0 => Some((name.clone(), true)),
+            (ReferenceType::ExternalRef, Some(name)) => Some((format!("{}+{}", name, field), true)),
+            (ref_type, _) => ref_type
+                .target_segment()
+                .and_then(|target_seg| self.label_for(target_seg, field))
+                .map(|name| (name, false)),
+        }
+    }
+
+    /// What a directive line starts with: the address column, or an indent.
+    fn directive_prefix(&self, seg: SegmentType, offset: u32) -> String {
+        if self.formatter.options.show_raw {
+            let address = self.object.segment_address(seg) + offset;
+            format!("{:>7}:  {:8}  ", self.formatter.address(address), "")
+        } else {
+            INDENT.to_string()
+        }
+    }
+
+    /// Disassembles the text word at `offset`.
+    pub fn instruction_line(&self, offset: u32) -> Result<String, DecodeError> {
+        let word = self.object.text[offset as usize];
+        let address = self.object.segment_address(SegmentType::Text) + offset;
+        let insn = self.formatter.isa().decode(word)?;
+        let (symbol, unresolved) = match self.reloc_symbol(SegmentType::Text, offset) {
+            Some((name, unresolved)) => (Some(name), unresolved),
+            None => (
+                branch_target(&insn, address)
+                    .and_then(|target| target.checked_sub(address - offset))
+                    .and_then(|target| self.label_at(SegmentType::Text, target))
+                    .map(str::to_string),
+                false,
+            ),
+        };
+        let mut line = String::new();
+        if !self.formatter.options.show_raw {
+            line.push_str(INDENT);
+        }
+        line.push_str(
+            &self
+                .formatter
+                .format(address, word, &insn, symbol.as_deref()),
+        );
+        if unresolved {
+            line.push_str("  # unresolved external");
+        }
+        Ok(line)
+    }
+
+    /// `.global` and `.extern` directives for every symbol in the object.
+    pub fn write_symbol_directives<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for label in &self.object.label_entries {
+            let directive = if label.resolved { ".global" } else { ".extern" };
+            writeln!(out, "{} {}", directive, label.name)?;
+        }
+        Ok(())
+    }
+
+    /// The text segment as assembly, with a label line before every
+    /// labelled instruction.
+    pub fn write_text<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, ".text")?;
+        let start = self.object.segment_address(SegmentType::Text);
+        for offset in 0..self.object.text.len() as u32 {
+            self.write_labels(out, SegmentType::Text, offset)?;
+            match self.instruction_line(offset) {
+                Ok(line) => writeln!(out, "{}", line)?,
+                Err(err) => {
+                    let mut line = String::new();
+                    if !self.formatter.options.show_raw {
+                        line.push_str(INDENT);
+                    }
+                    line.push_str(&self.formatter.format_unknown(start + offset, &err));
+                    writeln!(out, "{}", line)?
+                }
+            }
+        }
+        self.write_trailing_label(out, SegmentType::Text)
+    }
+
+    /// The data segment as `.word`, `.ascii`, `.asciiz` and `.space`
+    /// directives.
+    ///
+    /// Runs are broken at every label and relocated word so that each
+    /// label lands on the start of a directive.
+    pub fn write_data<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, ".data")?;
+        let words = &self.object.data;
+        let mut offset = 0;
+        while offset < words.len() {
+            self.write_labels(out, SegmentType::Data, offset as u32)?;
+            let limit = self.run_limit(offset);
+            let (directive, len) = self.data_directive(offset, limit);
+            writeln!(
+                out,
+                "{}{}",
+                self.directive_prefix(SegmentType::Data, offset as u32),
+                directive
+            )?;
+            offset += len;
+        }
+        self.write_trailing_label(out, SegmentType::Data)
+    }
+
+    /// The bss segment as `.space` directives between its labels.
+    pub fn write_bss<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, ".bss")?;
+        let size = self.object.bss_size;
+        let mut offset = 0;
+        while offset < size {
+            self.write_labels(out, SegmentType::Bss, offset)?;
+            let next = self
+                .labels
+                .get(&SegmentType::Bss)
+                .and_then(|labels| labels.range(offset + 1..).next())
+                .map_or(size, |(&next, _)| next.min(size));
+            writeln!(
+                out,
+                "{}.space {}",
+                self.directive_prefix(SegmentType::Bss, offset),
+                next - offset
+            )?;
+            offset = next;
+        }
+        self.write_trailing_label(out, SegmentType::Bss)
+    }
+
+    /// Prints a label that sits just past the end of `seg`, such as an
+    /// `end:` marker.
+    fn write_trailing_label<W: Write>(&self, out: &mut W, seg: SegmentType) -> io::Result<()> {
+        self.write_labels(out, seg, self.object.segment_size(seg))
+    }
+
+    /// The data offset a run starting at `offset` must stop before: the
+    /// next label or relocated word.
+    fn run_limit(&self, offset: usize) -> usize {
+        let next_label = self
+            .labels
+            .get(&SegmentType::Data)
+            .and_then(|labels| labels.range(offset as u32 + 1..).next())
+            .map(|(&next, _)| next as usize);
+        let next_reloc = self
+            .data_relocs
+            .range(offset as u32 + 1..)
+            .next()
+            .map(|&next| next as usize);
+        [next_label, next_reloc]
+            .into_iter()
+            .flatten()
+            .fold(self.object.data.len(), usize::min)
+    }
+
+    /// Chooses the directive that best describes the words from `offset`
+    /// up to `limit`, returning it with the number of words it covers.
+    fn data_directive(&self, offset: usize, limit: usize) -> (String, usize) {
+        let words = &self.object.data[..limit];
+        if let Some((name, _)) = self.reloc_symbol(SegmentType::Data, offset as u32) {
+            return (format!(".word {}", name), 1);
+        }
+
+        let zeros = words[offset..]
+            .iter()
+            .take_while(|&&word| word == 0)
+            .count();
+        if zeros > 1 {
+            return (format!(".space {}", zeros), zeros);
+        }
+
+        if starts_string(words, offset) {
+            let chars: String = words[offset..]
+                .iter()
+                .map_while(|&word| printable(word))
+                .collect();
+            let len = chars.chars().count();
+            return if words.get(offset + len) == Some(&0) {
+                (format!(".asciiz \"{}\"", escape(&chars)), len + 1)
+            } else {
+                (format!(".ascii \"{}\"", escape(&chars)), len)
+            };
+        }
+
+        let mut values = vec![self.formatter.word(words[offset])];
+        let mut next = offset + 1;
+        while next < words.len()
+            && values.len() < WORDS_PER_DIRECTIVE
+            && words[next] != 0
+            && !starts_string(words, next)
+        {
+            values.push(self.formatter.word(words[next]));
+            next += 1;
+        }
+        (format!(".word {}", values.join(", ")), next - offset)
+    }
+}
+
+/// Every text word that does not decode under `isa`, with its word address.
+pub fn undecodable_words(object: &ObjectFile, isa: &Isa) -> Vec<(u32, DecodeError)> {
+    let start = object.segment_address(SegmentType::Text);
+    object
+        .text
+        .iter()
+        .enumerate()
+        .filter_map(|(offset, &word)| {
+            isa.decode(word)
+                .err()
+                .map(|err| (start + offset as u32, err))
+        })
+        .collect()
+}
+
+/// The word address a PC-relative branch at `address` goes to. Absolute
+/// targets only name a label when a relocation says so.
+fn branch_target(insn: &Instruction, address: u32) -> Option<u32> {
+    insn.operands()
+        .any(|operand| matches!(operand, Operand::Target(Target::Relative(_))))
+        .then(|| insn.target_address(address))
+        .flatten()
+}
+
+/// The character a data word holds, if it is one `.ascii` can spell.
+fn printable(word: u32) -> Option<char> {
+    char::from_u32(word).filter(|ch| ch.is_ascii_graphic() || matches!(ch, ' ' | '\n' | '\t'))
+}
+
+/// A string is two or more printable words, or one followed by its NUL.
+fn starts_string(words: &[u32], offset: usize) -> bool {
+    printable(words[offset]).is_some()
+        && words
+            .get(offset + 1)
+            .is_some_and(|&next| next == 0 || printable(next).is_some())
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for ch in text.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
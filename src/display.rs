@@ -0,0 +1,258 @@
+//! readelf-style views of a parsed object.
+
+use crate::disasm::Disassembler;
+use crate::format::Formatter;
+use crate::object::{ObjectFile, RelocEntry, SegmentType};
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+
+pub fn write_header<W: Write>(out: &mut W, object: &ObjectFile) -> io::Result<()> {
+    let header = &object.header;
+    writeln!(out, "Header:")?;
+    writeln!(out, "  Magic:              {:#06x}", header.magic_number)?;
+    writeln!(out, "  Text size:          {} words", header.text_seg_size)?;
+    writeln!(out, "  Data size:          {} words", header.data_seg_size)?;
+    writeln!(out, "  Bss size:           {} words", header.bss_seg_size)?;
+    writeln!(out, "  Relocations:        {}", header.num_references)?;
+    writeln!(
+        out,
+        "  Symbol table size:  {} bytes",
+        header.symbol_name_table_size
+    )
+}
+
+/// One row per relocation entry. Addresses are relative to the segment the
+/// entry names.
+pub fn write_relocs<W: Write>(out: &mut W, object: &ObjectFile) -> io::Result<()> {
+    writeln!(out, "Relocations ({} entries):", object.reloc_entries.len())?;
+    writeln!(
+        out,
+        "  {:>5}  {:<7}  {:<14}  {:<7}  Symbol",
+        "Index", "Address", "Type", "Segment"
+    )?;
+    for (index, entry) in object.reloc_entries.iter().enumerate() {
+        writeln!(
+            out,
+            "  {:>5}  0x{:05x}  {:<14}  {:<7}  {}",
+            index,
+            entry.address,
+            entry.ref_type.name(),
+            entry.seg_type.map_or("-", SegmentType::name),
+            entry.symbol_name.as_deref().unwrap_or("-")
+        )?;
+    }
+    Ok(())
+}
+
+pub fn write_symbols<W: Write>(out: &mut W, object: &ObjectFile) -> io::Result<()> {
+    let width = object
+        .label_entries
+        .iter()
+        .map(|label| label.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("Name".len());
+    writeln!(out, "Symbols ({} entries):", object.label_entries.len())?;
+    writeln!(
+        out,
+        "  {:<width$}  {:<7}  {:<7}  Global",
+        "Name",
+        "Segment",
+        "Address",
+        width = width
+    )?;
+    for label in &object.label_entries {
+        let (segment, address) = match label.seg_type {
+            Some(seg) if label.resolved => (seg.name(), format!("0x{:05x}", label.address)),
+            _ => ("UNDEF", "-".to_string()),
+        };
+        writeln!(
+            out,
+            "  {:<width$}  {:<7}  {:<7}  {}",
+            label.name,
+            segment,
+            address,
+            if label.is_global { "yes" } else { "no" },
+            width = width
+        )?;
+    }
+    Ok(())
+}
+
+/// Where each segment lands when the object is laid out text, data, bss
+/// from address zero. End addresses are exclusive.
+pub fn write_segments<W: Write>(out: &mut W, object: &ObjectFile) -> io::Result<()> {
+    writeln!(out, "Segments:")?;
+    writeln!(out, "  {:<7}  {:<7}  {:<7}  Size", "Name", "Start", "End")?;
+    for seg in [SegmentType::Text, SegmentType::Data, SegmentType::Bss] {
+        let start = object.segment_address(seg);
+        let size = object.segment_size(seg);
+        writeln!(
+            out,
+            "  {:<7}  0x{:05x}  0x{:05x}  {}",
+            seg.name(),
+            start,
+            start + size,
+            size
+        )?;
+    }
+    Ok(())
+}
+
+const DUMP_WORDS_PER_LINE: usize = 4;
+
+/// Hex dump of a text or data segment, four words per line.
+///
+/// Each line starts with the word address of its first word. Words patched
+/// by a relocation entry are followed by `*` and listed at the end of the
+/// line. WRAMP stores one character per word, so the ASCII column shows one
+/// character per word.
+pub fn write_dump<W: Write>(out: &mut W, object: &ObjectFile, seg: SegmentType) -> io::Result<()> {
+    let words = match seg {
+        SegmentType::Text => &object.text,
+        SegmentType::Data => &object.data,
+        _ => {
+            return Err(io::Error::other(format!(
+                "cannot dump the {} segment",
+                seg.name()
+            )))
+        }
+    };
+    let start = object.segment_address(seg);
+    let mut relocs_at: HashMap<u32, Vec<&RelocEntry>> = HashMap::new();
+    for entry in &object.reloc_entries {
+        if !entry.ref_type.is_global() && entry.seg_type == Some(seg) {
+            relocs_at.entry(entry.address).or_default().push(entry);
+        }
+    }
+    writeln!(
+        out,
+        "Dump of {} segment ({} words):",
+        seg.name(),
+        words.len()
+    )?;
+
+    for (line, chunk) in words.chunks(DUMP_WORDS_PER_LINE).enumerate() {
+        let first = (line * DUMP_WORDS_PER_LINE) as u32;
+        let mut hex = String::new();
+        let mut ascii = String::new();
+        let mut notes = Vec::new();
+        for (index, &word) in chunk.iter().enumerate() {
+            let offset = first + index as u32;
+            let relocs = relocs_at.get(&offset).map_or(&[][..], Vec::as_slice);
+            hex.push_str(&format!(
+                "{:08x}{} ",
+                word,
+                if relocs.is_empty() { ' ' } else { '*' }
+            ));
+            ascii.push(match char::from_u32(word) {
+                Some(ch) if ch.is_ascii_graphic() || ch == ' ' => ch,
+                _ => '.',
+            });
+            for entry in relocs {
+                notes.push(format!(
+                    "0x{:05x}={}",
+                    start + offset,
+                    entry
+                        .symbol_name
+                        .as_deref()
+                        .unwrap_or(entry.ref_type.name())
+                ));
+            }
+        }
+        let mut row = format!(
+            "0x{:05x}:  {:<width$} |{}|",
+            start + first,
+            hex,
+            ascii,
+            width = DUMP_WORDS_PER_LINE * 10
+        );
+        if !notes.is_empty() {
+            row.push_str("  ");
+            row.push_str(&notes.join(" "));
+        }
+        writeln!(out, "{}", row)?;
+    }
+    Ok(())
+}
+
+/// One row of the `size` table. Sizes are in words.
+///
+/// The fields are wider than the header's so that a total over many files
+/// cannot overflow.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SizeSummary {
+    pub text: u64,
+    pub data: u64,
+    pub bss: u64,
+    pub relocs: u64,
+    pub symbols: u64,
+}
+
+impl SizeSummary {
+    pub fn from_object(object: &ObjectFile) -> Self {
+        Self {
+            text: object.segment_size(SegmentType::Text).into(),
+            data: object.segment_size(SegmentType::Data).into(),
+            bss: object.segment_size(SegmentType::Bss).into(),
+            relocs: object.reloc_entries.len() as u64,
+            symbols: object.label_entries.len() as u64,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.text + self.data + self.bss
+    }
+
+    pub fn add(&mut self, other: &SizeSummary) {
+        self.text += other.text;
+        self.data += other.data;
+        self.bss += other.bss;
+        self.relocs += other.relocs;
+        self.symbols += other.symbols;
+    }
+}
+
+/// The column headings for `write_size_row`, in the style of Berkeley `size`.
+pub fn write_size_heading<W: Write>(out: &mut W) -> io::Result<()> {
+    writeln!(
+        out,
+        "{:>8} {:>8} {:>8} {:>8} {:>8} {:>8} filename",
+        "text", "data", "bss", "total", "relocs", "symbols"
+    )
+}
+
+pub fn write_size_row<W: Write>(out: &mut W, summary: &SizeSummary, name: &str) -> io::Result<()> {
+    writeln!(
+        out,
+        "{:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {}",
+        summary.text,
+        summary.data,
+        summary.bss,
+        summary.total(),
+        summary.relocs,
+        summary.symbols,
+        name
+    )
+}
+
+/// Disassembles the text segment, one instruction per line, naming the
+/// symbols that relocated operands refer to, followed by the data and bss
+/// segments as directives.
+pub fn write_disassembly<W: Write>(
+    out: &mut W,
+    object: &ObjectFile,
+    formatter: &Formatter,
+) -> io::Result<()> {
+    let disassembler = Disassembler::new(object, formatter.clone());
+    disassembler.write_symbol_directives(out)?;
+    disassembler.write_text(out)?;
+    if !object.data.is_empty() {
+        disassembler.write_data(out)?;
+    }
+    if object.bss_size > 0 {
+        disassembler.write_bss(out)?;
+    }
+    Ok(())
+}
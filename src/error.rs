@@ -0,0 +1,246 @@
+use crate::object::{SegmentType, MAGIC_NUMBER, MAX_SEGMENT_SIZE};
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong while loading a WRAMP object file.
+///
+/// Every variant other than `Io` describes a corrupt object and carries the
+/// byte offset in the file where the problem was found.
+#[derive(Debug)]
+pub enum ObjError {
+    /// The file ended at `offset` before a full 24-byte header was read.
+    TruncatedHeader {
+        offset: usize,
+    },
+    BadMagic {
+        offset: usize,
+        found: u32,
+    },
+    /// A section starting at `offset` runs `size` bytes past a file of
+    /// `file_len` bytes.
+    SegmentPastEof {
+        section: &'static str,
+        offset: usize,
+        size: u64,
+        file_len: usize,
+    },
+    /// The header at `offset` gives a segment of `size` words, more than
+    /// the 20-bit address space holds.
+    SegmentTooLarge {
+        section: &'static str,
+        offset: usize,
+        size: u32,
+    },
+    BadRefType {
+        index: u32,
+        offset: usize,
+        code: u8,
+    },
+    BadSegType {
+        index: u32,
+        offset: usize,
+        code: u8,
+    },
+    /// A relocation's address does not fit inside the segment it names.
+    RelocOutOfRange {
+        index: u32,
+        offset: usize,
+        address: u32,
+        segment: SegmentType,
+        size: u32,
+    },
+    BadSymbolPointer {
+        index: u32,
+        offset: usize,
+        ptr: u32,
+        table_size: usize,
+    },
+    /// A symbol pointer lands inside a name instead of at its start.
+    SymbolPointerMidName {
+        index: u32,
+        offset: usize,
+        ptr: u32,
+    },
+    /// A label reference or external reference does not patch a word in
+    /// the text or data segment.
+    BadRefSegment {
+        index: u32,
+        offset: usize,
+        segment: Option<SegmentType>,
+    },
+    /// A global label is defined by more than one relocation entry.
+    DuplicateGlobal {
+        index: u32,
+        offset: usize,
+        name: String,
+        first_index: u32,
+    },
+    /// The symbol name table is malformed at `offset`.
+    BadSymbolTable {
+        offset: usize,
+        reason: &'static str,
+    },
+    /// `len` unexpected bytes follow the symbol name table.
+    TrailingGarbage {
+        offset: usize,
+        len: usize,
+    },
+    Io(io::Error),
+}
+
+impl ObjError {
+    /// The byte offset of the problem, for errors that describe a corrupt
+    /// object.
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            ObjError::TruncatedHeader { offset }
+            | ObjError::BadMagic { offset, .. }
+            | ObjError::SegmentPastEof { offset, .. }
+            | ObjError::SegmentTooLarge { offset, .. }
+            | ObjError::BadRefType { offset, .. }
+            | ObjError::BadSegType { offset, .. }
+            | ObjError::RelocOutOfRange { offset, .. }
+            | ObjError::BadSymbolPointer { offset, .. }
+            | ObjError::SymbolPointerMidName { offset, .. }
+            | ObjError::BadRefSegment { offset, .. }
+            | ObjError::DuplicateGlobal { offset, .. }
+            | ObjError::BadSymbolTable { offset, .. }
+            | ObjError::TrailingGarbage { offset, .. } => Some(*offset),
+            ObjError::Io(_) => None,
+        }
+    }
+
+    /// True when the bytes were read but do not form a valid object, as
+    /// opposed to the file not being readable at all.
+    pub fn is_corrupt(&self) -> bool {
+        !matches!(self, ObjError::Io(_))
+    }
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::TruncatedHeader { offset } => {
+                write!(f, "truncated header: file ends at offset {:#x}", offset)
+            }
+            ObjError::BadMagic { offset, found } => write!(
+                f,
+                "bad magic number at offset {:#x}: expected {:#x}, found {:#x}",
+                offset, MAGIC_NUMBER, found
+            ),
+            ObjError::SegmentPastEof {
+                section,
+                offset,
+                size,
+                file_len,
+            } => write!(
+                f,
+                "{} at offset {:#x} ({} bytes) runs past the end of the {}-byte file",
+                section, offset, size, file_len
+            ),
+            ObjError::SegmentTooLarge {
+                section,
+                offset,
+                size,
+            } => write!(
+                f,
+                "{} size at offset {:#x} is {:#x} words, more than the {:#x}-word address space",
+                section, offset, size, MAX_SEGMENT_SIZE
+            ),
+            ObjError::BadRefType {
+                index,
+                offset,
+                code,
+            } => write!(
+                f,
+                "relocation entry {} at offset {:#x}: unknown reference type {}",
+                index, offset, code
+            ),
+            ObjError::BadSegType {
+                index,
+                offset,
+                code,
+            } => write!(
+                f,
+                "relocation entry {} at offset {:#x}: unknown segment type {}",
+                index, offset, code
+            ),
+            ObjError::RelocOutOfRange {
+                index,
+                offset,
+                address,
+                segment,
+                size,
+            } => write!(
+                f,
+                "relocation entry {} at offset {:#x}: address {:#x} is outside the {}-word {:?} segment",
+                index, offset, address, size, segment
+            ),
+            ObjError::BadSymbolPointer {
+                index,
+                offset,
+                ptr,
+                table_size,
+            } => write!(
+                f,
+                "relocation entry {} at offset {:#x}: symbol pointer {:#x} is outside the {}-byte name table",
+                index, offset, ptr, table_size
+            ),
+            ObjError::SymbolPointerMidName { index, offset, ptr } => write!(
+                f,
+                "relocation entry {} at offset {:#x}: symbol pointer {:#x} points into the middle of a name",
+                index, offset, ptr
+            ),
+            ObjError::BadRefSegment {
+                index,
+                offset,
+                segment,
+            } => match segment {
+                Some(segment) => write!(
+                    f,
+                    "relocation entry {} at offset {:#x}: references cannot patch the {:?} segment",
+                    index, offset, segment
+                ),
+                None => write!(
+                    f,
+                    "relocation entry {} at offset {:#x}: reference has no segment",
+                    index, offset
+                ),
+            },
+            ObjError::DuplicateGlobal {
+                index,
+                offset,
+                name,
+                first_index,
+            } => write!(
+                f,
+                "relocation entry {} at offset {:#x}: global label `{}` already defined by entry {}",
+                index, offset, name, first_index
+            ),
+            ObjError::BadSymbolTable { offset, reason } => {
+                write!(f, "symbol name table at offset {:#x}: {}", offset, reason)
+            }
+            ObjError::TrailingGarbage { offset, len } => write!(
+                f,
+                "{} bytes of trailing garbage at offset {:#x}",
+                len, offset
+            ),
+            ObjError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ObjError {
+    fn from(err: io::Error) -> Self {
+        ObjError::Io(err)
+    }
+}
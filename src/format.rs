@@ -0,0 +1,222 @@
+//! Text rendering for decoded instructions.
+
+use crate::instructions::{DecodeError, Instruction, Operand, OperandKind, Target};
+use crate::isa::Isa;
+use std::fmt::Write;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Radix {
+    #[default]
+    Hex,
+    Decimal,
+}
+
+/// The choices that used to differ between `disassemble` and
+/// `disassemble_view`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Radix for immediates, offsets and addresses.
+    pub radix: Radix,
+    /// Print the symbol supplied for an instruction in place of its
+    /// address-carrying operand (a displacement or a jump or branch target).
+    pub resolve_symbols: bool,
+    /// Prefix each line with the instruction's address and raw word.
+    pub show_raw: bool,
+    /// Width the mnemonic is padded to before the operands.
+    pub mnemonic_width: usize,
+    /// Printed between operands.
+    pub operand_separator: &'static str,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            radix: Radix::Hex,
+            resolve_symbols: true,
+            show_raw: true,
+            mnemonic_width: 8,
+            operand_separator: ", ",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Formatter {
+    pub options: FormatOptions,
+    isa: Arc<Isa>,
+}
+
+impl Default for Formatter {
+    fn default() -> Self {
+        Self::new(FormatOptions::default())
+    }
+}
+
+impl Formatter {
+    /// A formatter for the built-in ISA.
+    pub fn new(options: FormatOptions) -> Self {
+        Self::with_isa(options, Isa::builtin())
+    }
+
+    /// A formatter that decodes with, and names registers after, `isa`.
+    pub fn with_isa(options: FormatOptions, isa: Arc<Isa>) -> Self {
+        Self { options, isa }
+    }
+
+    pub fn isa(&self) -> &Isa {
+        &self.isa
+    }
+
+    /// Decodes and formats `word`, found at word address `address`.
+    pub fn format_word(
+        &self,
+        address: u32,
+        word: u32,
+        symbol: Option<&str>,
+    ) -> Result<String, DecodeError> {
+        Ok(self.format(address, word, &self.isa.decode(word)?, symbol))
+    }
+
+    /// Formats a word no `INSN_TABLE` entry matches as a `.word` directive
+    /// so that the output still assembles back to the same bytes.
+    pub fn format_unknown(&self, address: u32, err: &DecodeError) -> String {
+        let DecodeError::UnknownEncoding { word, opcode, func } = *err;
+        let mut line = String::new();
+        if self.options.show_raw {
+            let _ = write!(line, "{:>7}:  {:08x}  ", self.address(address), word);
+        }
+        let _ = write!(
+            line,
+            "{:<width$} 0x{:08x}  # unknown encoding: opcode {:#x}, func {:#x}",
+            ".word",
+            word,
+            opcode,
+            func,
+            width = self.options.mnemonic_width
+        );
+        line
+    }
+
+    /// Formats a decoded instruction. `symbol`, when given and
+    /// `resolve_symbols` is set, replaces the operand that carries an
+    /// address.
+    pub fn format(
+        &self,
+        address: u32,
+        word: u32,
+        insn: &Instruction,
+        symbol: Option<&str>,
+    ) -> String {
+        let mut line = String::new();
+        if self.options.show_raw {
+            let _ = write!(line, "{:>7}:  {:08x}  ", self.address(address), word);
+        }
+        let mnemonic = insn.insn.mnemonic().unwrap_or("");
+        let operands = self.operands(address, insn, symbol);
+        if operands.is_empty() {
+            line.push_str(mnemonic);
+        } else {
+            let _ = write!(
+                line,
+                "{:<width$} {}",
+                mnemonic,
+                operands,
+                width = self.options.mnemonic_width
+            );
+        }
+        line
+    }
+
+    /// Renders the operands in `INSN_TABLE` order, writing a displacement
+    /// and its base register together, so `lw` comes out as `$3, 4($sp)`.
+    fn operands(&self, address: u32, insn: &Instruction, symbol: Option<&str>) -> String {
+        let symbol = symbol.filter(|_| self.options.resolve_symbols);
+        let mut operands = insn.insn.operands().iter().zip(insn.operands());
+        let mut rendered = Vec::new();
+        while let Some((&kind, operand)) = operands.next() {
+            let mut text = self.operand(address, insn, operand, symbol);
+            if kind == OperandKind::Offset20 {
+                if let Some((_, base)) = operands.next() {
+                    let _ = write!(text, "({})", self.operand(address, insn, base, symbol));
+                }
+            }
+            rendered.push(text);
+        }
+        rendered.join(self.options.operand_separator)
+    }
+
+    fn operand(
+        &self,
+        address: u32,
+        insn: &Instruction,
+        operand: Operand,
+        symbol: Option<&str>,
+    ) -> String {
+        match operand {
+            Operand::Gpr(reg) => self.isa.gpr_name(reg).to_string(),
+            Operand::Spr(reg) => self.isa.spr_name(reg).to_string(),
+            Operand::Imm(immediate) => self.immediate(insn, immediate),
+            Operand::Offset(offset) => match symbol {
+                Some(name) => name.to_string(),
+                None if base_is_zero(insn) && offset >= 0 => self.address(offset as u32),
+                None => self.signed(offset),
+            },
+            Operand::Target(target) => match (symbol, target) {
+                (Some(name), _) => name.to_string(),
+                (None, Target::Absolute(target)) => self.address(target),
+                // Unmasked, so a branch before address 0 reassembles.
+                (None, Target::Relative(offset)) => {
+                    match i64::from(address) + 1 + i64::from(offset) {
+                        target if target >= 0 => self.address(target as u32),
+                        target => self.signed(target as i32),
+                    }
+                }
+            },
+        }
+    }
+
+    fn immediate(&self, insn: &Instruction, immediate: u16) -> String {
+        match self.options.radix {
+            Radix::Hex => format!("0x{:04x}", immediate),
+            Radix::Decimal if is_unsigned_immediate(insn) => immediate.to_string(),
+            Radix::Decimal => (immediate as i16).to_string(),
+        }
+    }
+
+    fn signed(&self, value: i32) -> String {
+        match self.options.radix {
+            Radix::Hex if value < 0 => format!("-0x{:x}", value.unsigned_abs()),
+            Radix::Hex => format!("0x{:x}", value),
+            Radix::Decimal => value.to_string(),
+        }
+    }
+
+    /// Formats a whole data word.
+    pub fn word(&self, word: u32) -> String {
+        match self.options.radix {
+            Radix::Hex => format!("0x{:08x}", word),
+            Radix::Decimal => word.to_string(),
+        }
+    }
+
+    /// Formats a word address.
+    pub fn address(&self, address: u32) -> String {
+        match self.options.radix {
+            Radix::Hex => format!("0x{:05x}", address),
+            Radix::Decimal => address.to_string(),
+        }
+    }
+}
+
+/// A memory operand with `$0` as its base register addresses memory
+/// absolutely, so a non-negative offset is shown as an address.
+fn base_is_zero(insn: &Instruction) -> bool {
+    insn.operands().last() == Some(Operand::Gpr(0))
+}
+
+/// Whether the ISA description tags the instruction's immediate as
+/// zero-extended rather than sign-extended.
+fn is_unsigned_immediate(insn: &Instruction) -> bool {
+    insn.insn.has_tag("zero_extend")
+}
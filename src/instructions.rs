@@ -1,4 +1,9 @@
-#[derive(Clone, PartialEq, Eq)]
+use std::borrow::Cow;
+use std::fmt;
+use Field::{Rd, Rs, Rt};
+use OperandKind::{Absolute20, Gpr, Imm16, Offset20, Relative20, Spr};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum InsnDescriptor {
     INSN,
     IType,
@@ -7,762 +12,523 @@ pub enum InsnDescriptor {
     DIRECTIVE,
     OTHER,
 }
-#[derive(Clone, PartialEq, Eq)]
-pub struct InsnType<'a> {
-    mnemonic: Option<&'a str>,
-    operands: Option<&'a str>,
+
+/// The 4-bit register fields of an instruction word.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    /// Bits 24-27.
+    Rd,
+    /// Bits 20-23.
+    Rs,
+    /// Bits 0-3.
+    Rt,
+}
+
+impl Field {
+    pub const fn shift(self) -> u32 {
+        match self {
+            Field::Rd => 24,
+            Field::Rs => 20,
+            Field::Rt => 0,
+        }
+    }
+}
+
+/// One operand of an `INSN_TABLE` entry: what it is and where in the word
+/// it lives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OperandKind {
+    Gpr(Field),
+    Spr(Field),
+    /// The low 16 bits.
+    Imm16,
+    /// A signed 20-bit displacement. Always followed by its base register,
+    /// `Gpr(Rs)`, and written `offset(base)`.
+    Offset20,
+    /// A 20-bit word address.
+    Absolute20,
+    /// A signed 20-bit word offset from the next instruction.
+    Relative20,
+}
+
+/// An instruction table entry. The built-in entries borrow from the
+/// generated tables; those a variant adds own their data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InsnType {
+    mnemonic: Option<Cow<'static, str>>,
+    operands: Cow<'static, [OperandKind]>,
     opcode: u32,
     func: u32,
     type_descriptor: InsnDescriptor,
+    tags: Cow<'static, [Cow<'static, str>]>,
 }
 
-pub const GPR_NAME: [&str; 16] = [
-    "$0", "$1", "$2", "$3", "$4", "$5", "$6", "$7", "$8", "$9", "$10", "$11", "$12", "$13", "$sp",
-    "$ra",
-];
-
-pub const SPR_NAME: [&str; 16] = [
-    "$spr0", "$spr1", "$spr2", "$spr3", "$cctrl", "$estat", "$icount", "$ccount", "$evec", "$ear",
-    "$esp", "$ers", "$ptable", "$rbase", "$spr14", "$spr15",
-];
-
-pub const INSN_TABLE: [InsnType; 84] = [
-    InsnType {
-        mnemonic: Some("add"),
-        operands: Some("d,s,t"),
-        opcode: 0x0,
-        func: 0x0,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("addi"),
-        operands: Some("d,s,i"),
-        opcode: 0x1,
-        func: 0x0,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("addu"),
-        operands: Some("d,s,t"),
-        opcode: 0x0,
-        func: 0x1,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("addui"),
-        operands: Some("d,s,i"),
-        opcode: 0x1,
-        func: 0x1,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("sub"),
-        operands: Some("S,s,t"),
-        opcode: 0x0,
-        func: 0x2,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("subi"),
-        operands: Some("d,s,i"),
-        opcode: 0x1,
-        func: 0x2,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("subu"),
-        operands: Some("d,s,t"),
-        opcode: 0x0,
-        func: 0x3,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("subui"),
-        operands: Some("d,s,i"),
-        opcode: 0x1,
-        func: 0x3,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("mult"),
-        operands: Some("d,s,t"),
-        opcode: 0x0,
-        func: 0x4,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("multi"),
-        operands: Some("d,s,i"),
-        opcode: 0x1,
-        func: 0x4,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("multu"),
-        operands: Some("d,s,t"),
-        opcode: 0x0,
-        func: 0x5,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("multui"),
-        operands: Some("d,s,i"),
-        opcode: 0x1,
-        func: 0x5,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("div"),
-        operands: Some("d,s,t"),
-        opcode: 0x0,
-        func: 0x6,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("divi"),
-        operands: Some("d,s,i"),
-        opcode: 0x1,
-        func: 0x6,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("divu"),
-        operands: Some("d,s,t"),
-        opcode: 0x0,
-        func: 0x7,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("divui"),
-        operands: Some("d,s,i"),
-        opcode: 0x1,
-        func: 0x7,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("rem"),
-        operands: Some("d,s,t"),
-        opcode: 0x0,
-        func: 0x8,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("remi"),
-        operands: Some("d,s,i"),
-        opcode: 0x1,
-        func: 0x8,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("remu"),
-        operands: Some("d,s,t"),
-        opcode: 0x0,
-        func: 0x9,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("remui"),
-        operands: Some("d,s,i"),
-        opcode: 0x1,
-        func: 0x9,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("lhi"),
-        operands: Some("d,i"),
-        opcode: 0x3,
-        func: 0xe,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("la"),
-        operands: Some("d,j"),
-        opcode: 0xc,
-        func: 0x0,
-        type_descriptor: InsnDescriptor::JType,
-    },
-    // Bitwise instructions
-    InsnType {
-        mnemonic: Some("and"),
-        operands: Some("d,s,t"),
-        opcode: 0x0,
-        func: 0xb,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("andi"),
-        operands: Some("d,s,i"),
-        opcode: 0x1,
-        func: 0xb,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("or"),
-        operands: Some("d,s,t"),
-        opcode: 0x0,
-        func: 0xd,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("ori"),
-        operands: Some("d,s,i"),
-        opcode: 0x1,
-        func: 0xd,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("xor"),
-        operands: Some("d,s,t"),
-        opcode: 0x0,
-        func: 0xf,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("xori"),
-        operands: Some("d,s,i"),
-        opcode: 0x1,
-        func: 0xf,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("sll"),
-        operands: Some("d,s,t"),
-        opcode: 0x0,
-        func: 0xa,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("slli"),
-        operands: Some("d,s,i"),
-        opcode: 0x1,
-        func: 0xa,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("srl"),
-        operands: Some("d,s,t"),
-        opcode: 0x0,
-        func: 0xc,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("srli"),
-        operands: Some("d,s,i"),
-        opcode: 0x1,
-        func: 0xc,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("sra"),
-        operands: Some("d,s,t"),
-        opcode: 0x0,
-        func: 0xe,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("srai"),
-        operands: Some("d,s,i"),
-        opcode: 0x1,
-        func: 0xe,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    // Test instructions
-    InsnType {
-        mnemonic: Some("slt"),
-        operands: Some("d,s,t"),
-        opcode: 0x2,
-        func: 0x0,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("slti"),
-        operands: Some("d,s,i"),
-        opcode: 0x3,
-        func: 0x0,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("sltu"),
-        operands: Some("d,s,t"),
-        opcode: 0x2,
-        func: 0x1,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("sltui"),
-        operands: Some("d,s,i"),
-        opcode: 0x3,
-        func: 0x1,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("sgt"),
-        operands: Some("d,s,t"),
-        opcode: 0x2,
-        func: 0x2,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("sgti"),
-        operands: Some("d,s,i"),
-        opcode: 0x3,
-        func: 0x2,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("sgtu"),
-        operands: Some("d,s,t"),
-        opcode: 0x2,
-        func: 0x3,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("sgtui"),
-        operands: Some("d,s,i"),
-        opcode: 0x3,
-        func: 0x3,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("sle"),
-        operands: Some("d,s,t"),
-        opcode: 0x2,
-        func: 0x4,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("slei"),
-        operands: Some("d,s,i"),
-        opcode: 0x3,
-        func: 0x4,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("sleu"),
-        operands: Some("d,s,t"),
-        opcode: 0x2,
-        func: 0x5,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("sleui"),
-        operands: Some("d,s,i"),
-        opcode: 0x3,
-        func: 0x5,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("sge"),
-        operands: Some("d,s,t"),
-        opcode: 0x2,
-        func: 0x6,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("sgei"),
-        operands: Some("d,s,i"),
-        opcode: 0x3,
-        func: 0x6,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("sgeu"),
-        operands: Some("d,s,t"),
-        opcode: 0x2,
-        func: 0x7,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("sgeui"),
-        operands: Some("d,s,i"),
-        opcode: 0x3,
-        func: 0x7,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("seq"),
-        operands: Some("d,s,t"),
-        opcode: 0x2,
-        func: 0x8,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("seqi"),
-        operands: Some("d,s,i"),
-        opcode: 0x3,
-        func: 0x8,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("sequ"),
-        operands: Some("d,s,t"),
-        opcode: 0x2,
-        func: 0x9,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("sequi"),
-        operands: Some("d,s,i"),
-        opcode: 0x3,
-        func: 0x9,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("sne"),
-        operands: Some("d,s,t"),
-        opcode: 0x2,
-        func: 0xa,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("snei"),
-        operands: Some("d,s,i"),
-        opcode: 0x3,
-        func: 0xa,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("sneu"),
-        operands: Some("d,s,t"),
-        opcode: 0x2,
-        func: 0xb,
-        type_descriptor: InsnDescriptor::RType,
-    },
-    InsnType {
-        mnemonic: Some("sneui"),
-        operands: Some("d,s,i"),
-        opcode: 0x3,
-        func: 0xb,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    // Branch instructions
-    InsnType {
-        mnemonic: Some("j"),
-        operands: Some("j"),
-        opcode: 0x4,
-        func: 0x0,
-        type_descriptor: InsnDescriptor::JType,
-    },
-    InsnType {
-        mnemonic: Some("jr"),
-        operands: Some("s"),
-        opcode: 0x5,
-        func: 0x0,
-        type_descriptor: InsnDescriptor::JType,
-    },
-    InsnType {
-        mnemonic: Some("jal"),
-        operands: Some("j"),
-        opcode: 0x6,
-        func: 0x0,
-        type_descriptor: InsnDescriptor::JType,
-    },
-    InsnType {
-        mnemonic: Some("jalr"),
-        operands: Some("s"),
-        opcode: 0x7,
-        func: 0x0,
-        type_descriptor: InsnDescriptor::JType,
-    },
-    InsnType {
-        mnemonic: Some("beqz"),
-        operands: Some("s,b"),
-        opcode: 0xa,
-        func: 0x0,
-        type_descriptor: InsnDescriptor::JType,
-    },
-    InsnType {
-        mnemonic: Some("bnez"),
-        operands: Some("s,b"),
-        opcode: 0xb,
-        func: 0x0,
-        type_descriptor: InsnDescriptor::JType,
-    },
-    // Memory instructions
-    InsnType {
-        mnemonic: Some("lw"),
-        operands: Some("d,o(s)"),
-        opcode: 0x8,
-        func: 0x0,
-        type_descriptor: InsnDescriptor::JType,
-    },
-    InsnType {
-        mnemonic: Some("sw"),
-        operands: Some("d,o(s)"),
-        opcode: 0x9,
-        func: 0x0,
-        type_descriptor: InsnDescriptor::JType,
-    },
-    // Special instructions
-    InsnType {
-        mnemonic: Some("movgs"),
-        operands: Some("D,s"),
-        opcode: 0x3,
-        func: 0xc,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("movsg"),
-        operands: Some("d,S"),
-        opcode: 0x3,
-        func: 0xd,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("break"),
-        operands: Some(""),
-        opcode: 0x2,
-        func: 0xc,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("syscall"),
-        operands: Some(""),
-        opcode: 0x2,
-        func: 0xd,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some("rfe"),
-        operands: Some(""),
-        opcode: 0x2,
-        func: 0xe,
-        type_descriptor: InsnDescriptor::IType,
-    },
-    InsnType {
-        mnemonic: Some(".word"),
-        operands: None,
-        opcode: 0xfff,
-        func: 0xfff,
-        type_descriptor: InsnDescriptor::DIRECTIVE,
-    },
-    InsnType {
-        mnemonic: Some(".ascii"),
-        operands: None,
-        opcode: 0xfff,
-        func: 0xfff,
-        type_descriptor: InsnDescriptor::DIRECTIVE,
-    },
-    InsnType {
-        mnemonic: Some(".asciiz"),
-        operands: None,
-        opcode: 0xfff,
-        func: 0xfff,
-        type_descriptor: InsnDescriptor::DIRECTIVE,
-    },
-    InsnType {
-        mnemonic: Some(".space"),
-        operands: None,
-        opcode: 0xfff,
-        func: 0xfff,
-        type_descriptor: InsnDescriptor::DIRECTIVE,
-    },
-    InsnType {
-        mnemonic: Some(".equ"),
-        operands: None,
-        opcode: 0xfff,
-        func: 0xfff,
-        type_descriptor: InsnDescriptor::DIRECTIVE,
-    },
-    InsnType {
-        mnemonic: Some(".global"),
-        operands: None,
-        opcode: 0xfff,
-        func: 0xfff,
-        type_descriptor: InsnDescriptor::DIRECTIVE,
-    },
-    InsnType {
-        mnemonic: Some(".extern"),
-        operands: None,
-        opcode: 0xfff,
-        func: 0xfff,
-        type_descriptor: InsnDescriptor::DIRECTIVE,
-    },
-    InsnType {
-        mnemonic: Some(".data"),
-        operands: None,
-        opcode: 0xfff,
-        func: 0xfff,
-        type_descriptor: InsnDescriptor::DIRECTIVE,
-    },
-    InsnType {
-        mnemonic: Some(".text"),
-        operands: None,
-        opcode: 0xfff,
-        func: 0xfff,
-        type_descriptor: InsnDescriptor::DIRECTIVE,
-    },
-    InsnType {
-        mnemonic: Some(".bss"),
-        operands: None,
-        opcode: 0xfff,
-        func: 0xfff,
-        type_descriptor: InsnDescriptor::DIRECTIVE,
-    },
-    InsnType {
-        mnemonic: Some(".frame"),
-        operands: None,
-        opcode: 0xfff,
-        func: 0xfff,
-        type_descriptor: InsnDescriptor::DIRECTIVE,
-    },
-    InsnType {
-        mnemonic: Some(".mask"),
-        operands: None,
-        opcode: 0xfff,
-        func: 0xfff,
-        type_descriptor: InsnDescriptor::DIRECTIVE,
-    },
-    InsnType {
-        mnemonic: None,
-        operands: None,
-        opcode: 0,
-        func: 0,
-        type_descriptor: InsnDescriptor::DIRECTIVE,
-    },
-];
-fn disassemble(insn_address: u32, instruction: u32) -> () {
-    let opcode: u32 = (instruction >> 28) & 0xf;
-    let func: u32 = (instruction >> 16) & 0xf;
-    let rd: u32 = (instruction >> 24) & 0xf;
-    let rs: u32 = (instruction >> 20) & 0xf;
-    let rt: u32 = (instruction & 0xf);
-    let address: u32 = instruction & 0xfffff;
-    let immediate: u32 = instruction & 0xffff;
-    let signed_address: i32 = if (address & 0x80000) != 0 {
-        (0xfff00000 | address).try_into().unwrap()
-    } else {
-        address.try_into().unwrap()
-    };
-    let insn_vec = INSN_TABLE.to_vec();
-    let gpr_vec = GPR_NAME.to_vec();
-    let spr_vec = SPR_NAME.to_vec();
-
-    if let Some(insn_idx) = insn_vec.iter().position(|insn| {
-        insn.mnemonic.is_some()
-            && insn.opcode == opcode
-            && (insn.type_descriptor == InsnDescriptor::JType || insn.func == func)
-    }) {
-        print!("Found a match at insn_idx: {}", insn_idx);
-        let result = format!(
-            ":\t{}",
-            INSN_TABLE[insn_idx].mnemonic.expect("real string (real)")
-        );
-        print!("{}", result);
-        for ch in INSN_TABLE[insn_idx].operands.unwrap().chars() {
-            match ch {
-                'd' => {
-                    print!("{}", gpr_vec.get(rd as usize).unwrap())
-                }
+// `GPR_NAME`, `SPR_NAME` and `INSN_TABLE`, generated by build.rs from
+// isa/wramp.toml.
+include!(concat!(env!("OUT_DIR"), "/isa.rs"));
 
-                's' => {
-                    print!("{}", gpr_vec.get(rs as usize).unwrap())
-                }
-                'D' => {
-                    print!("{}", spr_vec.get(rd as usize).unwrap())
-                }
-                'S' => {
-                    print!("{}", spr_vec.get(rs as usize).unwrap())
-                }
-                't' => {
-                    print!("{}", gpr_vec.get(rt as usize).unwrap())
-                }
-                'o' => match address {
-                    0 => print!("{}", 0),
-                    _ => {
-                        if rs != 0 {
-                            print!("{}", signed_address);
-                        } else {
-                            let formatted = format!("0x{:05X}", address);
-                            println!("{}", formatted);
-                        }
-                    }
-                },
-                'b' => {
-                    let address_i_think =
-                        (((insn_address as i32 + signed_address) & 0xfffff) + 1) as u32;
-                    let formatted = format!("0x{:05X}", address_i_think);
-                    println!("{}", formatted);
-                }
-                'i' => {
-                    print!("0x{:04x}", immediate);
+/// The most operands any `INSN_TABLE` entry takes.
+pub const MAX_OPERANDS: usize = 3;
+
+/// A branch or jump target as encoded in the 20-bit address field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    /// `Absolute20` operands: a word address.
+    Absolute(u32),
+    /// `Relative20` operands: a signed word offset from the instruction
+    /// after the branch.
+    Relative(i32),
+}
+
+/// A decoded operand, in the order its `INSN_TABLE` entry lists it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+    Gpr(u8),
+    Spr(u8),
+    /// The raw 16-bit immediate; whether it is sign-extended is up to the
+    /// instruction.
+    Imm(u16),
+    /// The sign-extended 20-bit displacement of an `offset(base)` memory
+    /// operand.
+    Offset(i32),
+    Target(Target),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// No `INSN_TABLE` entry matches the word's opcode and func fields.
+    UnknownEncoding { word: u32, opcode: u32, func: u32 },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnknownEncoding { word, opcode, func } => write!(
+                f,
+                "unknown encoding {:#010x} (opcode {:#x}, func {:#x})",
+                word, opcode, func
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// A decoded machine word: the `INSN_TABLE` entry it matched and its typed
+/// operands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Instruction<'a> {
+    pub insn: &'a InsnType,
+    operands: [Option<Operand>; MAX_OPERANDS],
+}
+
+impl<'a> Instruction<'a> {
+    pub fn new(insn: &'a InsnType, operands: &[Operand]) -> Self {
+        let mut slots = [None; MAX_OPERANDS];
+        for (slot, &operand) in slots.iter_mut().zip(operands) {
+            *slot = Some(operand);
+        }
+        Self {
+            insn,
+            operands: slots,
+        }
+    }
+
+    pub fn descriptor(&self) -> &InsnDescriptor {
+        &self.insn.type_descriptor
+    }
+
+    pub fn operands(&self) -> impl Iterator<Item = Operand> + '_ {
+        self.operands.iter().flatten().copied()
+    }
+
+    /// The word address a branch or jump target refers to, for an
+    /// instruction at `address`.
+    pub fn target_address(&self, address: u32) -> Option<u32> {
+        self.operands().find_map(|operand| match operand {
+            Operand::Target(Target::Absolute(target)) => Some(target),
+            Operand::Target(Target::Relative(offset)) => {
+                Some((address.wrapping_add(1).wrapping_add(offset as u32)) & 0xfffff)
+            }
+            _ => None,
+        })
+    }
+}
+
+impl InsnType {
+    pub const fn new(
+        mnemonic: &'static str,
+        operands: &'static [OperandKind],
+        opcode: u32,
+        func: u32,
+        type_descriptor: InsnDescriptor,
+        tags: &'static [Cow<'static, str>],
+    ) -> Self {
+        Self {
+            mnemonic: Some(Cow::Borrowed(mnemonic)),
+            operands: Cow::Borrowed(operands),
+            opcode,
+            func,
+            type_descriptor,
+            tags: Cow::Borrowed(tags),
+        }
+    }
+
+    /// An entry that owns its mnemonic, operand list and tags, for
+    /// instructions described at run time.
+    pub(crate) fn owned(
+        mnemonic: String,
+        operands: Vec<OperandKind>,
+        opcode: u32,
+        func: u32,
+        type_descriptor: InsnDescriptor,
+        tags: Vec<String>,
+    ) -> Self {
+        Self {
+            mnemonic: Some(Cow::Owned(mnemonic)),
+            operands: Cow::Owned(operands),
+            opcode,
+            func,
+            type_descriptor,
+            tags: tags.into_iter().map(Cow::Owned).collect(),
+        }
+    }
+
+    pub fn mnemonic(&self) -> Option<&str> {
+        self.mnemonic.as_deref()
+    }
+
+    pub const fn operands(&self) -> &[OperandKind] {
+        match &self.operands {
+            Cow::Borrowed(operands) => operands,
+            Cow::Owned(operands) => operands.as_slice(),
+        }
+    }
+
+    pub fn opcode(&self) -> u32 {
+        self.opcode
+    }
+
+    pub fn func(&self) -> u32 {
+        self.func
+    }
+
+    pub fn type_descriptor(&self) -> &InsnDescriptor {
+        &self.type_descriptor
+    }
+
+    /// The semantics tags the ISA description gives the instruction, such
+    /// as `branch` or `zero_extend`.
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.tags.iter().map(|tag| &**tag)
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags().any(|found| found == tag)
+    }
+}
+
+/// Sign-extends the 20-bit address field.
+fn sign_extend_20(address: u32) -> i32 {
+    ((address << 12) as i32) >> 12
+}
+
+/// Marks an opcode/func pair no `INSN_TABLE` entry matches.
+pub(crate) const NO_MATCH: u8 = u8::MAX;
+
+/// `INSN_TABLE` indices keyed by `opcode << 4 | func`.
+///
+/// J-type entries ignore func, so they fill all sixteen slots of their
+/// opcode. When two entries claim a slot the earlier one in `INSN_TABLE`
+/// wins, as it did with the old linear scan.
+static DECODE_TABLE: [u8; 256] = build_decode_table(&INSN_TABLE);
+
+// build.rs refuses a larger table; this keeps `index as u8` honest if it
+// ever stops doing so.
+const _: () = assert!(INSN_TABLE.len() < NO_MATCH as usize);
+
+pub(crate) const fn build_decode_table(table: &[InsnType]) -> [u8; 256] {
+    let mut lookup = [NO_MATCH; 256];
+    let mut index = 0;
+    while index < table.len() {
+        let insn = &table[index];
+        let is_insn = insn.mnemonic.is_some() && insn.opcode < 16 && insn.func < 16;
+        if is_insn {
+            let ignores_func = matches!(insn.type_descriptor, InsnDescriptor::JType);
+            let mut func = 0;
+            while func < 16 {
+                let slot = ((insn.opcode << 4) | func) as usize;
+                if (ignores_func || insn.func == func) && lookup[slot] == NO_MATCH {
+                    lookup[slot] = index as u8;
                 }
-                'j' => {
-                    print!("0x{:05}", address);
+                func += 1;
+            }
+        }
+        index += 1;
+    }
+    lookup
+}
+
+const _: () = check_operand_schemas(&INSN_TABLE);
+
+/// Fails the build if an `INSN_TABLE` entry's operands do not fit its
+/// descriptor.
+const fn check_operand_schemas(table: &[InsnType]) {
+    let mut index = 0;
+    while index < table.len() {
+        if let Some(problem) = schema_problem(&table[index]) {
+            panic!("{}", problem);
+        }
+        index += 1;
+    }
+}
+
+/// Why `insn` lists operands its descriptor has no room for, if it does:
+/// R-types take only registers, I-types one 16-bit immediate that shares
+/// bits with rt, J-types one 20-bit field that does the same. No register
+/// field may be read twice, and every `Offset20` is followed by its
+/// `Gpr(Rs)` base.
+pub(crate) const fn schema_problem(insn: &InsnType) -> Option<&'static str> {
+    let schema = insn.operands();
+    if schema.len() > MAX_OPERANDS {
+        return Some("entry has too many operands");
+    }
+    let mut used = [false; 3];
+    let mut immediates = 0;
+    let mut wide = 0;
+    let mut slot = 0;
+    while slot < schema.len() {
+        match schema[slot] {
+            Gpr(field) | Spr(field) => {
+                if used[field as usize] {
+                    return Some("entry reads a register field twice");
                 }
-                _ => {
-                    print!("{}", ch);
+                used[field as usize] = true;
+            }
+            Imm16 => immediates += 1,
+            Offset20 => {
+                if !(slot + 1 < schema.len() && matches!(schema[slot + 1], Gpr(Rs))) {
+                    return Some("offset20 operand is not followed by gpr(rs)");
                 }
+                wide += 1;
             }
+            Absolute20 | Relative20 => wide += 1,
         }
+        slot += 1;
+    }
+    let rt = used[Rt as usize];
+    let fits = match insn.type_descriptor {
+        InsnDescriptor::RType => immediates == 0 && wide == 0,
+        InsnDescriptor::IType => !rt && immediates <= 1 && wide == 0,
+        InsnDescriptor::JType => !rt && immediates == 0 && wide <= 1,
+        _ => schema.is_empty(),
+    };
+    if fits {
+        None
     } else {
-        println!("No match found.");
+        Some("operands do not fit the entry's format")
     }
 }
 
-fn disassemble_view(insn_address: u32, instruction: u32, label_name: Option<&str>) -> () {
+/// Decodes one machine word against `INSN_TABLE`.
+pub fn decode(instruction: u32) -> Result<Instruction<'static>, DecodeError> {
+    decode_with(&INSN_TABLE, &DECODE_TABLE, instruction)
+}
+
+/// Decodes one machine word against `table`, whose lookup was built by
+/// `build_decode_table`.
+pub(crate) fn decode_with<'a>(
+    table: &'a [InsnType],
+    lookup: &[u8; 256],
+    instruction: u32,
+) -> Result<Instruction<'a>, DecodeError> {
     let opcode: u32 = (instruction >> 28) & 0xf;
     let func: u32 = (instruction >> 16) & 0xf;
-    let rd: u32 = (instruction >> 24) & 0xf;
-    let rs: u32 = (instruction >> 20) & 0xf;
-    let rt: u32 = (instruction & 0xf);
     let address: u32 = instruction & 0xfffff;
-    let immediate: u32 = instruction & 0xffff;
-    let signed_address: i32 = if (address & 0x80000) != 0 {
-        (0xfff00000 | address).try_into().unwrap()
-    } else {
-        address.try_into().unwrap()
+
+    let insn = match lookup[((opcode << 4) | func) as usize] {
+        NO_MATCH => {
+            return Err(DecodeError::UnknownEncoding {
+                word: instruction,
+                opcode,
+                func,
+            })
+        }
+        index => &table[index as usize],
     };
-    let insn_vec = INSN_TABLE.to_vec();
-    let gpr_vec = GPR_NAME.to_vec();
-    let spr_vec = SPR_NAME.to_vec();
-
-    if let Some(insn_idx) = insn_vec.iter().position(|insn| {
-        insn.mnemonic.is_some()
-            && insn.opcode == opcode
-            && (insn.type_descriptor == InsnDescriptor::JType || insn.func == func)
-    }) {
-        print!("Found a match at insn_idx: {}", insn_idx);
-        let result = format!(
-            ":\t{}",
-            INSN_TABLE[insn_idx].mnemonic.expect("real string (real)")
-        );
-        print!("{}", result);
-        for ch in INSN_TABLE[insn_idx].operands.unwrap().chars() {
-            match ch {
-                'd' => {
-                    print!("{}", gpr_vec.get(rd as usize).unwrap())
-                }
-                's' => {
-                    print!("{}", gpr_vec.get(rs as usize).unwrap())
-                }
-                'D' => {
-                    print!("{}", spr_vec.get(rd as usize).unwrap())
-                }
-                'S' => {
-                    print!("{}", spr_vec.get(rs as usize).unwrap())
-                }
-                't' => {
-                    print!("{}", gpr_vec.get(rt as usize).unwrap())
-                }
-                'i' => {
-                    print!("0x{:04x}", immediate);
-                }
-                'j' | 'o' | 'b' => match label_name {
-                    Some(name) => {
-                        print!("{}", name);
-                    }
-                    None => {
-                        let formatted = format!("0x{:05X}", address);
-                        println!("{}", formatted);
-                    }
-                },
-                _ => {
-                    print!("{}", ch);
-                }
+
+    let register = |field: Field| ((instruction >> field.shift()) & 0xf) as u8;
+    let mut operands = [None; MAX_OPERANDS];
+    for (slot, kind) in operands.iter_mut().zip(insn.operands()) {
+        *slot = Some(match *kind {
+            Gpr(field) => Operand::Gpr(register(field)),
+            Spr(field) => Operand::Spr(register(field)),
+            Imm16 => Operand::Imm((instruction & 0xffff) as u16),
+            Offset20 => Operand::Offset(sign_extend_20(address)),
+            Absolute20 => Operand::Target(Target::Absolute(address)),
+            Relative20 => Operand::Target(Target::Relative(sign_extend_20(address))),
+        });
+    }
+    Ok(Instruction { insn, operands })
+}
+
+/// Bounds of a sign-extended 20-bit field.
+const FIELD_20_MIN: i32 = -0x80000;
+const FIELD_20_MAX: i32 = 0x7ffff;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncodeError {
+    /// The `INSN_TABLE` entry is a directive, not a machine instruction.
+    NotAnInstruction,
+    /// The instruction has `found` operands but its `INSN_TABLE` entry
+    /// lists `expected`.
+    WrongOperandCount {
+        expected: usize,
+        found: usize,
+    },
+    /// Operand `index` is not the kind its `INSN_TABLE` entry asks for.
+    OperandMismatch {
+        index: usize,
+        expected: OperandKind,
+        found: Operand,
+    },
+    RegisterOutOfRange {
+        index: usize,
+        register: u8,
+    },
+    /// The value fits neither a signed nor an unsigned 16-bit immediate.
+    ImmediateOutOfRange {
+        value: i64,
+    },
+    /// A displacement or branch offset does not fit in 20 signed bits.
+    OffsetOutOfRange {
+        index: usize,
+        offset: i32,
+    },
+    /// A jump address does not fit in 20 bits.
+    TargetOutOfRange {
+        index: usize,
+        target: u32,
+    },
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::NotAnInstruction => write!(f, "not a machine instruction"),
+            EncodeError::WrongOperandCount { expected, found } => {
+                write!(f, "expected {} operands, found {}", expected, found)
+            }
+            EncodeError::OperandMismatch {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "operand {}: expected {:?}, found {:?}",
+                index + 1,
+                expected,
+                found
+            ),
+            EncodeError::RegisterOutOfRange { index, register } => write!(
+                f,
+                "operand {}: register {} is out of range",
+                index + 1,
+                register
+            ),
+            EncodeError::ImmediateOutOfRange { value } => {
+                write!(f, "immediate {} does not fit in 16 bits", value)
             }
+            EncodeError::OffsetOutOfRange { index, offset } => write!(
+                f,
+                "operand {}: offset {} does not fit in 20 bits",
+                index + 1,
+                offset
+            ),
+            EncodeError::TargetOutOfRange { index, target } => write!(
+                f,
+                "operand {}: address {:#x} does not fit in 20 bits",
+                index + 1,
+                target
+            ),
         }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+/// Narrows `value` to a 16-bit immediate, accepting both the signed and the
+/// unsigned reading of the field.
+pub fn immediate(value: i64) -> Result<u16, EncodeError> {
+    if (-0x8000..=0xffff).contains(&value) {
+        Ok(value as u16)
     } else {
-        println!("No match found.");
+        Err(EncodeError::ImmediateOutOfRange { value })
+    }
+}
+
+/// Encodes an instruction back into its machine word. The inverse of
+/// `decode` for every word `decode` accepts, up to the bits the
+/// instruction ignores, which are left zero.
+pub fn encode(instruction: &Instruction) -> Result<u32, EncodeError> {
+    let insn = instruction.insn;
+    let descriptor = insn.type_descriptor;
+    if !matches!(
+        descriptor,
+        InsnDescriptor::RType | InsnDescriptor::IType | InsnDescriptor::JType
+    ) {
+        return Err(EncodeError::NotAnInstruction);
+    }
+
+    let expected = insn.operands().len();
+    let found = instruction.operands().count();
+    if found != expected {
+        return Err(EncodeError::WrongOperandCount { expected, found });
+    }
+
+    let mut word = insn.opcode << 28;
+    if descriptor != InsnDescriptor::JType {
+        word |= insn.func << 16;
+    }
+    for (index, (&kind, operand)) in insn
+        .operands()
+        .iter()
+        .zip(instruction.operands())
+        .enumerate()
+    {
+        let register = |field: Field, register: u8| {
+            if register < 16 {
+                Ok((register as u32) << field.shift())
+            } else {
+                Err(EncodeError::RegisterOutOfRange { index, register })
+            }
+        };
+        let offset = |offset: i32| {
+            if (FIELD_20_MIN..=FIELD_20_MAX).contains(&offset) {
+                Ok(offset as u32 & 0xfffff)
+            } else {
+                Err(EncodeError::OffsetOutOfRange { index, offset })
+            }
+        };
+        word |= match (kind, operand) {
+            (Gpr(field), Operand::Gpr(r)) | (Spr(field), Operand::Spr(r)) => register(field, r)?,
+            (Imm16, Operand::Imm(imm)) => imm as u32,
+            (Offset20, Operand::Offset(displacement)) => offset(displacement)?,
+            (Relative20, Operand::Target(Target::Relative(displacement))) => offset(displacement)?,
+            (Absolute20, Operand::Target(Target::Absolute(target))) => {
+                if target > 0xfffff {
+                    return Err(EncodeError::TargetOutOfRange { index, target });
+                }
+                target
+            }
+            _ => {
+                return Err(EncodeError::OperandMismatch {
+                    index,
+                    expected: kind,
+                    found: operand,
+                })
+            }
+        };
     }
+    Ok(word)
 }
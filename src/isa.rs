@@ -0,0 +1,211 @@
+//! The instruction set the decoder and formatter work from: the built-in
+//! WRAMP tables, optionally with a variant loaded at run time layered over
+//! them.
+
+use crate::instructions::{
+    build_decode_table, decode_with, schema_problem, DecodeError, Field, InsnDescriptor, InsnType,
+    Instruction, OperandKind, GPR_NAME, INSN_TABLE, ISA_NAME, NO_MATCH, SPR_NAME,
+};
+use crate::isa_spec::{self, Format, InsnSpec};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, LazyLock};
+
+static BUILTIN: LazyLock<Arc<Isa>> = LazyLock::new(|| {
+    Arc::new(Isa {
+        name: ISA_NAME.to_string(),
+        gpr_names: GPR_NAME.map(str::to_string),
+        spr_names: SPR_NAME.map(str::to_string),
+        table: INSN_TABLE.to_vec(),
+        lookup: build_decode_table(&INSN_TABLE),
+    })
+});
+
+#[derive(Debug)]
+pub enum IsaError {
+    Io(io::Error),
+    /// The variant file is not a valid ISA description.
+    Invalid(String),
+}
+
+impl fmt::Display for IsaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IsaError::Io(err) => write!(f, "{}", err),
+            IsaError::Invalid(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for IsaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IsaError::Io(err) => Some(err),
+            IsaError::Invalid(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for IsaError {
+    fn from(err: io::Error) -> Self {
+        IsaError::Io(err)
+    }
+}
+
+/// An instruction table with its decode lookup and register names.
+#[derive(Clone, Debug)]
+pub struct Isa {
+    name: String,
+    gpr_names: [String; 16],
+    spr_names: [String; 16],
+    table: Vec<InsnType>,
+    lookup: [u8; 256],
+}
+
+impl Isa {
+    /// The tables compiled in from isa/wramp.toml.
+    pub fn builtin() -> Arc<Isa> {
+        Arc::clone(&BUILTIN)
+    }
+
+    /// Loads a variant file and layers it over this ISA. The variant is
+    /// named after the file unless it sets `name` itself.
+    pub fn load_variant(&self, path: &Path) -> Result<Isa, IsaError> {
+        let text = fs::read_to_string(path)?;
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.with_variant(&name, &text)
+    }
+
+    /// Layers the variant described by `text` over this ISA. Register
+    /// names it gives replace the current ones; an instruction whose
+    /// mnemonic already exists replaces that entry, and any other is added.
+    /// Variant instructions take precedence where encodings overlap.
+    pub fn with_variant(&self, name: &str, text: &str) -> Result<Isa, IsaError> {
+        let spec = isa_spec::parse(text).map_err(IsaError::Invalid)?;
+        let mut gpr_names = self.gpr_names.clone();
+        let mut spr_names = self.spr_names.clone();
+        for (names, overrides) in [(&mut gpr_names, spec.gpr), (&mut spr_names, spec.spr)] {
+            for (name, new) in names.iter_mut().zip(overrides) {
+                if let Some(new) = new {
+                    *name = new;
+                }
+            }
+        }
+
+        let mut table = Vec::new();
+        for insn in &spec.instructions {
+            let entry = variant_entry(insn);
+            if let Some(problem) = schema_problem(&entry) {
+                return Err(IsaError::Invalid(format!(
+                    "instruction {}: {}",
+                    insn.mnemonic, problem
+                )));
+            }
+            table.push(entry);
+        }
+        let replaced = |entry: &InsnType| {
+            spec.instructions
+                .iter()
+                .any(|insn| entry.mnemonic() == Some(insn.mnemonic.as_str()))
+        };
+        table.extend(self.table.iter().filter(|entry| !replaced(entry)).cloned());
+        for directive in &spec.directives {
+            if !table
+                .iter()
+                .any(|entry| entry.mnemonic() == Some(directive.as_str()))
+            {
+                table.push(InsnType::owned(
+                    directive.clone(),
+                    Vec::new(),
+                    0xfff,
+                    0xfff,
+                    InsnDescriptor::DIRECTIVE,
+                    Vec::new(),
+                ));
+            }
+        }
+        if table.len() >= NO_MATCH as usize {
+            return Err(IsaError::Invalid(format!(
+                "{} entries is more than the decoder can index",
+                table.len()
+            )));
+        }
+
+        Ok(Isa {
+            name: spec.name.unwrap_or_else(|| name.to_string()),
+            gpr_names,
+            spr_names,
+            lookup: build_decode_table(&table),
+            table,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn table(&self) -> &[InsnType] {
+        &self.table
+    }
+
+    /// The entry for `mnemonic`, instruction or directive.
+    pub fn find(&self, mnemonic: &str) -> Option<&InsnType> {
+        self.table
+            .iter()
+            .find(|entry| entry.mnemonic() == Some(mnemonic))
+    }
+
+    pub fn gpr_name(&self, register: u8) -> &str {
+        &self.gpr_names[register as usize & 0xf]
+    }
+
+    pub fn spr_name(&self, register: u8) -> &str {
+        &self.spr_names[register as usize & 0xf]
+    }
+
+    /// Decodes one machine word against this ISA's table.
+    pub fn decode(&self, word: u32) -> Result<Instruction<'_>, DecodeError> {
+        decode_with(&self.table, &self.lookup, word)
+    }
+}
+
+/// Builds a table entry for a variant instruction.
+fn variant_entry(insn: &InsnSpec) -> InsnType {
+    let descriptor = match insn.format {
+        Format::R => InsnDescriptor::RType,
+        Format::I => InsnDescriptor::IType,
+        Format::J => InsnDescriptor::JType,
+    };
+    InsnType::owned(
+        insn.mnemonic.clone(),
+        insn.operands
+            .iter()
+            .map(|name| operand_kind(name))
+            .collect(),
+        insn.opcode,
+        insn.func,
+        descriptor,
+        insn.tags.clone(),
+    )
+}
+
+/// The `OperandKind` for a name from `isa_spec::OPERAND_NAMES`.
+fn operand_kind(name: &str) -> OperandKind {
+    match name {
+        "gpr(rd)" => OperandKind::Gpr(Field::Rd),
+        "gpr(rs)" => OperandKind::Gpr(Field::Rs),
+        "gpr(rt)" => OperandKind::Gpr(Field::Rt),
+        "spr(rd)" => OperandKind::Spr(Field::Rd),
+        "spr(rs)" => OperandKind::Spr(Field::Rs),
+        "imm16" => OperandKind::Imm16,
+        "offset20" => OperandKind::Offset20,
+        "absolute20" => OperandKind::Absolute20,
+        "relative20" => OperandKind::Relative20,
+        _ => unreachable!("isa_spec accepted unknown operand {:?}", name),
+    }
+}
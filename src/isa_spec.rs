@@ -0,0 +1,196 @@
+//! The TOML ISA description format.
+//!
+//! build.rs compiles isa/wramp.toml into the built-in tables with this
+//! parser, and `Isa::with_variant` uses it to layer a variant file over
+//! them at run time. It only depends on `toml` so that build.rs can include
+//! it by path.
+
+use toml::{Table, Value};
+
+/// The operand names an `operands` list may use, matching `OperandKind`.
+pub const OPERAND_NAMES: [&str; 9] = [
+    "gpr(rd)",
+    "gpr(rs)",
+    "gpr(rt)",
+    "spr(rd)",
+    "spr(rs)",
+    "imm16",
+    "offset20",
+    "absolute20",
+    "relative20",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    R,
+    I,
+    J,
+}
+
+/// One `[[instruction]]` table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InsnSpec {
+    pub mnemonic: String,
+    pub format: Format,
+    pub opcode: u32,
+    /// Zero for J-types, which ignore it.
+    pub func: u32,
+    /// Names from `OPERAND_NAMES`, in assembler order.
+    pub operands: Vec<&'static str>,
+    pub tags: Vec<String>,
+}
+
+/// A parsed ISA description. Every part is optional so that a variant can
+/// list only what it changes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IsaSpec {
+    pub name: Option<String>,
+    /// Register names by number; `None` where the file leaves one alone.
+    pub gpr: [Option<String>; 16],
+    pub spr: [Option<String>; 16],
+    pub instructions: Vec<InsnSpec>,
+    pub directives: Vec<String>,
+}
+
+pub fn parse(text: &str) -> Result<IsaSpec, String> {
+    let isa: Table = text
+        .parse()
+        .map_err(|err: toml::de::Error| err.to_string())?;
+    let mut spec = IsaSpec {
+        name: match isa.get("name") {
+            None => None,
+            Some(_) => Some(string(&isa, "name")?.to_string()),
+        },
+        ..IsaSpec::default()
+    };
+
+    if let Some(registers) = isa.get("registers") {
+        let registers = registers.as_table().ok_or("registers is not a table")?;
+        spec.gpr = register_names(registers, "gpr")?;
+        spec.spr = register_names(registers, "spr")?;
+    }
+    for (index, insn) in array(&isa, "instruction")?.iter().enumerate() {
+        let insn =
+            instruction(insn).map_err(|err| format!("instruction {}: {}", index + 1, err))?;
+        spec.instructions.push(insn);
+    }
+    for (index, directive) in array(&isa, "directive")?.iter().enumerate() {
+        let name = directive
+            .as_table()
+            .ok_or_else(|| "not a table".to_string())
+            .and_then(|directive| string(directive, "name"))
+            .map_err(|err| format!("directive {}: {}", index + 1, err))?;
+        spec.directives.push(name.to_string());
+    }
+    Ok(spec)
+}
+
+/// Register names given either as a full array of sixteen or as a table
+/// keyed by register number, such as `{ 12 = "$fp" }`.
+fn register_names(registers: &Table, key: &str) -> Result<[Option<String>; 16], String> {
+    let mut names: [Option<String>; 16] = Default::default();
+    match registers.get(key) {
+        None => {}
+        Some(Value::Array(_)) => {
+            let list = strings(registers, key)?;
+            if list.len() != 16 {
+                return Err(format!(
+                    "registers.{} lists {} names, not 16",
+                    key,
+                    list.len()
+                ));
+            }
+            for (slot, name) in names.iter_mut().zip(list) {
+                *slot = Some(name.to_string());
+            }
+        }
+        Some(Value::Table(table)) => {
+            for (number, name) in table {
+                let slot = number
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&number| number < 16)
+                    .ok_or_else(|| format!("registers.{}: no register {:?}", key, number))?;
+                let name = name
+                    .as_str()
+                    .ok_or_else(|| format!("registers.{}.{} is not a string", key, number))?;
+                names[slot] = Some(name.to_string());
+            }
+        }
+        Some(_) => return Err(format!("registers.{} is not an array or table", key)),
+    }
+    Ok(names)
+}
+
+fn instruction(value: &Value) -> Result<InsnSpec, String> {
+    let insn = value.as_table().ok_or("not a table")?;
+    let mnemonic = string(insn, "mnemonic")?;
+    let context = |err: String| format!("{}: {}", mnemonic, err);
+    let format = match string(insn, "format").map_err(context)? {
+        "R" => Format::R,
+        "I" => Format::I,
+        "J" => Format::J,
+        other => return Err(context(format!("unknown format {:?}", other))),
+    };
+    let opcode = field(insn, "opcode").map_err(context)?;
+    let func = match insn.get("func") {
+        None if format == Format::J => 0,
+        _ => field(insn, "func").map_err(context)?,
+    };
+    let operands = strings(insn, "operands")
+        .map_err(context)?
+        .into_iter()
+        .map(|operand| {
+            OPERAND_NAMES
+                .into_iter()
+                .find(|&name| name == operand)
+                .ok_or_else(|| context(format!("unknown operand {:?}", operand)))
+        })
+        .collect::<Result<_, _>>()?;
+    let tags = match insn.get("tags") {
+        None => Vec::new(),
+        Some(_) => strings(insn, "tags").map_err(context)?,
+    };
+    Ok(InsnSpec {
+        mnemonic: mnemonic.to_string(),
+        format,
+        opcode,
+        func,
+        operands,
+        tags: tags.into_iter().map(str::to_string).collect(),
+    })
+}
+
+fn array<'a>(parent: &'a Table, key: &str) -> Result<&'a [Value], String> {
+    match parent.get(key) {
+        None => Ok(&[]),
+        Some(value) => value
+            .as_array()
+            .map(Vec::as_slice)
+            .ok_or_else(|| format!("{} is not an array of tables", key)),
+    }
+}
+
+fn string<'a>(parent: &'a Table, key: &str) -> Result<&'a str, String> {
+    parent
+        .get(key)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("missing string {}", key))
+}
+
+fn strings<'a>(parent: &'a Table, key: &str) -> Result<Vec<&'a str>, String> {
+    parent
+        .get(key)
+        .and_then(Value::as_array)
+        .and_then(|values| values.iter().map(Value::as_str).collect())
+        .ok_or_else(|| format!("{} is not an array of strings", key))
+}
+
+/// A 4-bit opcode or func field.
+fn field(parent: &Table, key: &str) -> Result<u32, String> {
+    match parent.get(key).and_then(Value::as_integer) {
+        Some(value @ 0..=15) => Ok(value as u32),
+        Some(value) => Err(format!("{} {:#x} does not fit in 4 bits", key, value)),
+        None => Err(format!("missing integer {}", key)),
+    }
+}
@@ -0,0 +1,151 @@
+//! Machine-readable JSON rendering of a parsed object.
+//!
+//! The document layout is built by hand rather than derived from the Rust
+//! types so that it only changes when `FORMAT_VERSION` does.
+
+use crate::instructions::{DecodeError, Instruction, Operand, Target};
+use crate::isa::Isa;
+use crate::object::{ObjectFile, SegmentType};
+use serde_json::{json, Value};
+
+/// Bumped whenever a field is renamed or removed.
+pub const FORMAT_VERSION: u32 = 1;
+
+pub fn object_to_json(object: &ObjectFile) -> Value {
+    let header = &object.header;
+    json!({
+        "format_version": FORMAT_VERSION,
+        "header": {
+            "magic_number": header.magic_number,
+            "text_seg_size": header.text_seg_size,
+            "data_seg_size": header.data_seg_size,
+            "bss_seg_size": header.bss_seg_size,
+            "num_references": header.num_references,
+            "symbol_name_table_size": header.symbol_name_table_size,
+        },
+        "segments": {
+            "text": segment_json(object, SegmentType::Text, &object.text),
+            "data": segment_json(object, SegmentType::Data, &object.data),
+            "bss": {
+                "start": object.segment_address(SegmentType::Bss),
+                "size": object.bss_size,
+            },
+        },
+        "relocations": object
+            .reloc_entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                json!({
+                    "index": index,
+                    "address": entry.address,
+                    "symbol_ptr": entry.symbol_ptr,
+                    "ref_type": entry.ref_type.name(),
+                    "segment": entry.seg_type.map(SegmentType::name),
+                    "symbol": entry.symbol_name,
+                })
+            })
+            .collect::<Vec<_>>(),
+        "symbols": object
+            .label_entries
+            .iter()
+            .map(|label| {
+                json!({
+                    "name": label.name,
+                    "segment": label.seg_type.map(SegmentType::name),
+                    "address": label.resolved.then_some(label.address),
+                    "resolved": label.resolved,
+                    "global": label.is_global,
+                })
+            })
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// The text segment as a list of instructions decoded under `isa`,
+/// addressed like the `segments.text.words` list.
+///
+/// Words that do not decode are marked `"unknown": true` and carry the
+/// opcode and func fields that failed to match.
+pub fn instructions_to_json(object: &ObjectFile, isa: &Isa) -> Value {
+    let start = object.segment_address(SegmentType::Text);
+    object
+        .text
+        .iter()
+        .enumerate()
+        .map(|(offset, &word)| {
+            let address = start + offset as u32;
+            match isa.decode(word) {
+                Ok(insn) => json!({
+                    "address": address,
+                    "word": word,
+                    "unknown": false,
+                    "mnemonic": insn.insn.mnemonic(),
+                    "operands": insn
+                        .operands()
+                        .map(|operand| operand_json(isa, address, &insn, operand))
+                        .collect::<Vec<_>>(),
+                }),
+                Err(DecodeError::UnknownEncoding { opcode, func, .. }) => json!({
+                    "address": address,
+                    "word": word,
+                    "unknown": true,
+                    "mnemonic": null,
+                    "opcode": opcode,
+                    "func": func,
+                }),
+            }
+        })
+        .collect()
+}
+
+/// One decoded operand, in `INSN_TABLE` order. Immediates are given as the
+/// instruction interprets them: zero-extended if the ISA tags them so,
+/// sign-extended otherwise.
+fn operand_json(isa: &Isa, address: u32, insn: &Instruction, operand: Operand) -> Value {
+    match operand {
+        Operand::Gpr(register) => json!({
+            "kind": "gpr",
+            "number": register,
+            "name": isa.gpr_name(register),
+        }),
+        Operand::Spr(register) => json!({
+            "kind": "spr",
+            "number": register,
+            "name": isa.spr_name(register),
+        }),
+        Operand::Imm(immediate) => json!({
+            "kind": "immediate",
+            "value": if insn.insn.has_tag("zero_extend") {
+                i32::from(immediate)
+            } else {
+                i32::from(immediate as i16)
+            },
+        }),
+        Operand::Offset(offset) => json!({ "kind": "offset", "value": offset }),
+        Operand::Target(Target::Absolute(target)) => json!({
+            "kind": "target",
+            "relative": false,
+            "address": target,
+        }),
+        Operand::Target(Target::Relative(offset)) => json!({
+            "kind": "target",
+            "relative": true,
+            "offset": offset,
+            "address": insn.target_address(address),
+        }),
+    }
+}
+
+fn segment_json(object: &ObjectFile, seg: SegmentType, words: &[u32]) -> Value {
+    let start = object.segment_address(seg);
+    json!({
+        "start": start,
+        "size": words.len(),
+        "words": words
+            .iter()
+            .enumerate()
+            .map(|(offset, &value)| json!({ "address": start + offset as u32, "value": value }))
+            .collect::<Vec<_>>(),
+    })
+}
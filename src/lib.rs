@@ -0,0 +1,18 @@
+pub mod asm;
+mod check;
+pub mod disasm;
+pub mod display;
+pub mod error;
+pub mod format;
+pub mod instructions;
+pub mod isa;
+mod isa_spec;
+pub mod json;
+pub mod object;
+mod writer;
+
+pub use error::ObjError;
+pub use object::{
+    LabelEntry, ObjectFile, ObjectHeader, ReferenceType, RelocEntry, SegmentType, SymbolTable, BSS,
+    DATA, HEADER_SIZE, MAGIC_NUMBER, REF_TYPE_NAME, RELOC_ENTRY_SIZE, SEG_TYPE_NAME, TEXT,
+};
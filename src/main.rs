@@ -1,98 +1,30 @@
-#![allow(dead_code)]
-mod instructions;
-use byteorder::ByteOrder;
-use byteorder::{LittleEndian, ReadBytesExt};
-use clap::{Arg, ArgAction, Command};
-use std::error::Error;
-use std::{fs, io};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use rwobj::display::SizeSummary;
+use rwobj::format::{FormatOptions, Formatter, Radix};
+use rwobj::isa::{Isa, IsaError};
+use rwobj::{asm, disasm, display, ObjError, ObjectFile, SegmentType};
 use std::fs::File;
-use std::io::prelude::*;
-use std::io::Read;
-
-pub const SEG_TYPE_NAME: [&str; 5] = ["NONE", "TEXT", "DATA", "BSS", "NUM_SEGMENTS"];
-pub const TEXT: usize = 1;
-pub const DATA: usize = 2;
-pub const BSS: usize = 3;
-
-#[derive(Clone, Copy, Debug)]
-pub struct ObjectHeader {
-    magic_number: u32,
-    text_seg_size: u32,
-    data_seg_size: u32,
-    bss_seg_size: u32,
-    num_references: u32,
-    symbol_name_table_size: u32,
-}
-
-impl ObjectHeader {
-    fn from_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
-        let mut buffer = [0u8; 24];
-        reader.read_exact(&mut buffer)?;
-        Ok(Self {
-            magic_number: LittleEndian::read_u32(&buffer[0..4]),
-            text_seg_size: LittleEndian::read_u32(&buffer[4..8]),
-            data_seg_size: LittleEndian::read_u32(&buffer[8..12]),
-            bss_seg_size: LittleEndian::read_u32(&buffer[12..16]),
-            num_references: LittleEndian::read_u32(&buffer[16..20]),
-            symbol_name_table_size: LittleEndian::read_u32(&buffer[20..24]),
-        })
-    }
-}
-
-pub enum ReferenceType {
-    GlobalData,
-    GlobalText,
-    GlobalBss,
-    TextLabelRef,
-    DataLabelRef,
-    BssLabelRef,
-    ExternalRef,
-}
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
 
-pub enum SegmentType {
-    Text,
-    Data,
-    Bss,
-    NumSegments,
-}
-
-pub struct RelocEntry {
-    address: u32,
-    symbol_ptr: u32,
-    ref_type: ReferenceType,
-    seg_type: Option<SegmentType>,
-}
-
-pub struct LabelEntry<'a> {
-    name: &'a str,
-    address: i32,
-    seg_type: SegmentType,
-    resolved: bool,
-    is_global: bool,
-    file_no: i32,
-}
-
-pub struct Reference<'a> {
-    label_entry: Option<&'a mut LabelEntry<'a>>,
-    source_seg: SegmentType,
-    target_seg: SegmentType,
-    address: i32,
-}
+// Exit statuses follow sysexits.h, so each failure has its own and none
+// is the 2 clap uses for usage errors.
 
-pub struct FileType<'a> {
-    filename: &'a str,
-    file_header: ObjectHeader,
-    // holds starting addresses of each segment
-    segment: Vec<Vec<u32>>,
-    segment_address: Vec<u32>,
-    reference: Option<&'a mut Reference<'a>>,
-    label_entries: Vec<LabelEntry<'a>>,
-    reloc_entries: Vec<RelocEntry>,
-}
+/// Exit status when the file was read but is not a valid object, or is
+/// assembly source or an ISA variant with errors (`EX_DATAERR`).
+const EXIT_CORRUPT: u8 = 65;
+/// Exit status when the file is missing or could not be read
+/// (`EX_NOINPUT`).
+const EXIT_UNREADABLE: u8 = 66;
+/// Exit status when the output could not be written (`EX_IOERR`).
+const EXIT_OUTPUT: u8 = 74;
 
-pub const MAGIC_NUMBER: u32 = 0xdaa1;
+type View = fn(&mut io::StdoutLock<'static>, &ObjectFile) -> io::Result<()>;
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() -> ExitCode {
     let matches = Command::new("wobj")
         .version("1.0")
         .author("cf1048596")
@@ -110,103 +42,395 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .help("Display disassembly")
                 .action(ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("header")
+                .long("header")
+                .help("Display the object header")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("relocs")
+                .long("relocs")
+                .help("Display the relocation table")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("symbols")
+                .long("symbols")
+                .help("Display the symbol table")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("segments")
+                .long("segments")
+                .help("Display the segment layout")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("Output format")
+                .value_parser(["text", "json"])
+                .default_value("text"),
+        )
+        .arg(
+            Arg::new("radix")
+                .long("radix")
+                .help("Radix for numbers in the disassembly")
+                .value_parser(["hex", "dec"])
+                .default_value("hex"),
+        )
+        .arg(
+            Arg::new("raw")
+                .long("raw")
+                .help("Show the address and word columns even when -d is the only view")
+                .conflicts_with("no-raw")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-raw")
+                .long("no-raw")
+                .help("Leave the address and word columns out of the disassembly")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .help("Fail instead of emitting .word for text words that do not decode")
+                .requires("disassemble")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(isa_arg())
+        .arg(
+            Arg::new("check")
+                .long("check")
+                .help("Verify the object's internal consistency without printing it")
+                .action(ArgAction::SetTrue),
+        )
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
+        .subcommand(
+            Command::new("dump")
+                .about("Hex dump a segment, marking relocated words")
+                .arg(
+                    Arg::new("file")
+                        .help("The file to process")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("segment")
+                        .long("segment")
+                        .help("The segment to dump")
+                        .value_parser(["text", "data"])
+                        .default_value("text"),
+                ),
+        )
+        .subcommand(
+            Command::new("wasm")
+                .about("Assemble a WRAMP source file into an object file")
+                .arg(
+                    Arg::new("file")
+                        .help("The source file to assemble")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Output object file (default: the source with a .o extension)"),
+                )
+                .arg(isa_arg()),
+        )
+        .subcommand(
+            Command::new("size")
+                .about("Summarise segment sizes across object files")
+                .arg(
+                    Arg::new("files")
+                        .help("The files or glob patterns to process")
+                        .required(true)
+                        .num_args(1..),
+                ),
+        )
         .get_matches();
 
+    match matches.subcommand() {
+        Some(("dump", sub_matches)) => return dump(sub_matches),
+        Some(("size", sub_matches)) => return size(sub_matches),
+        Some(("wasm", sub_matches)) => return wasm(sub_matches),
+        _ => {}
+    }
+
     let file_name = matches.get_one::<String>("file").expect("File is required");
-    let disassemble = *matches.get_one::<bool>("disassemble").unwrap_or(&false);
-    let mut text_size = 0;
-    let mut data_size = 0;
-    let mut bss_size = 0;
-
-    // Check if the file exists
-    if fs::metadata(file_name).is_ok() {
-        println!("Processing file: {}", file_name);
-        let file = File::open(file_name)?;
-        let mut reader = io::BufReader::new(file);
-        let header = ObjectHeader::from_reader(&mut reader)?;
-        println!("{:#?}", header);
-        if header.magic_number == MAGIC_NUMBER {
-            println!("Magic number is correct");
-            let mut file_type: FileType = FileType {
-                filename: file_name,
-                file_header: header,
-                segment: {
-                    let mut segment_vec = Vec::new();
-                    // text
-                    segment_vec.push(Vec::with_capacity(header.text_seg_size as usize));
-                    // data
-                    segment_vec.push(Vec::with_capacity(header.data_seg_size as usize));
-                    // bss
-                    segment_vec.push(Vec::with_capacity(header.bss_seg_size as usize));
-                    segment_vec
-                },
-                segment_address: {
-                    let mut segment_address_vec = Vec::new();
-                    // text
-                    segment_address_vec.push(0);
-                    // data
-                    segment_address_vec.push(header.text_seg_size as u32);
-                    // bss
-                    segment_address_vec.push( header.text_seg_size as u32 + header.data_seg_size as u32,
-                    );
-                    segment_address_vec
-                },
-                reference: None,
-                label_entries: Vec::new(),
-                reloc_entries: Vec::new(),
-            };
-
-            // Read the text segment
-            reader.seek(io::SeekFrom::Start(24))?;
-            for i in 0..header.text_seg_size {
-                let value = reader.read_u32::<LittleEndian>()?;
-                file_type.segment[TEXT].push(value);
-                println!("text seg found {}", value);
-            }
-            println!("text vec segment size {}", file_type.segment[TEXT].len());
+    let disassemble = matches.get_flag("disassemble");
+    let check = matches.get_flag("check");
+    let json = matches
+        .get_one::<String>("format")
+        .is_some_and(|f| f == "json");
+    let show_header = matches.get_flag("header");
+    let show_relocs = matches.get_flag("relocs");
+    let show_symbols = matches.get_flag("symbols");
+    let show_segments = matches.get_flag("segments");
+    let radix = match matches.get_one::<String>("radix").map(String::as_str) {
+        Some("dec") => Radix::Decimal,
+        _ => Radix::Hex,
+    };
 
+    let isa = match load_isa(&matches) {
+        Ok(isa) => isa,
+        Err(code) => return code,
+    };
 
-            // Read the data segment
-            reader.seek(io::SeekFrom::Start(24 + header.text_seg_size as u64))?;
-            for i in 0..header.data_seg_size {
-                let value = reader.read_u32::<LittleEndian>()?;
-                file_type.segment[DATA].push(value);
-                println!("data seg found {}", value);
-            }
-            println!("data vec segment size {}", file_type.segment[DATA].len());
-
-            //Increment the size counters
-            text_size += header.text_seg_size;
-            data_size += header.data_seg_size;
-            bss_size += header.bss_seg_size;
-
-            let num_reloc_entries = header.num_references;
-            reader.seek(io::SeekFrom::Start(24 + header.text_seg_size as u64 + header.data_seg_size as u64))?;
-            for i in 0..num_reloc_entries {
-                let read_address = reader.read_u32::<LittleEndian>()?;
-                let sym_ptr = reader.read_u32::<LittleEndian>()?;
-                let read_ref_type = reader.read_u8::<LittleEndian>()?;
-                let sym_ptr = reader.read_u8::<LittleEndian>()?;
-                file_type.reloc_entries.push(
-                    RelocEntry {
-                        address : read_address,
-                        symbol_ptr : sym_ptr,
-                        ref_type : let  match {
-
-                        }
-
-                    }
-                );
-                println!("data seg found {}", value);
-                */
-            }
+    let object = match open_object(file_name) {
+        Ok(object) => object,
+        Err(code) => return code,
+    };
 
-            if disassemble {}
-        } else {
-            println!("Magic number is not correct");
+    if check {
+        let problems = object.check();
+        for problem in &problems {
+            eprintln!("wobj: {}: {}", file_name, problem);
+        }
+        if !problems.is_empty() {
+            return ExitCode::from(EXIT_CORRUPT);
+        }
+        println!("{}: ok", file_name);
+        return ExitCode::SUCCESS;
+    }
+
+    if matches.get_flag("strict") {
+        let unknown = disasm::undecodable_words(&object, &isa);
+        for (address, err) in &unknown {
+            eprintln!("wobj: {}: {:#07x}: {}", file_name, address, err);
         }
+        if !unknown.is_empty() {
+            return ExitCode::from(EXIT_CORRUPT);
+        }
+    }
+
+    if json {
+        let mut document = rwobj::json::object_to_json(&object);
+        document["file"] = file_name.as_str().into();
+        document["isa"] = isa.name().into();
+        if disassemble {
+            document["instructions"] = rwobj::json::instructions_to_json(&object, &isa);
+        }
+        if let Err(err) = writeln!(io::stdout().lock(), "{:#}", document) {
+            eprintln!("wobj: {}", err);
+            return ExitCode::from(EXIT_OUTPUT);
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    let all = !(show_header || show_relocs || show_symbols || show_segments || disassemble);
+    let views: Vec<View> = [
+        (show_header, display::write_header as View),
+        (show_segments, display::write_segments),
+        (show_relocs, display::write_relocs),
+        (show_symbols, display::write_symbols),
+    ]
+    .into_iter()
+    .filter(|&(shown, _)| all || shown)
+    .map(|(_, view)| view)
+    .collect();
+
+    // On its own, the disassembly is plain source `wobj wasm` reassembles,
+    // so the columns it would reject are left out unless asked for.
+    let show_raw = if views.is_empty() {
+        matches.get_flag("raw")
     } else {
-        eprintln!("Error: File '{}' does not exist.", file_name);
+        !matches.get_flag("no-raw")
+    };
+    let formatter = Formatter::with_isa(
+        FormatOptions {
+            radix,
+            show_raw,
+            ..FormatOptions::default()
+        },
+        isa,
+    );
+    let disassembly = disassemble.then_some(&formatter);
+    if let Err(err) = print_views(&object, &views, disassembly) {
+        eprintln!("wobj: {}", err);
+        return ExitCode::from(EXIT_OUTPUT);
+    }
+    ExitCode::SUCCESS
+}
+
+/// Prints each view in turn, then the disassembly if one was asked for,
+/// separated by blank lines.
+fn print_views(
+    object: &ObjectFile,
+    views: &[View],
+    disassembly: Option<&Formatter>,
+) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    for (index, view) in views.iter().enumerate() {
+        if index > 0 {
+            writeln!(out)?;
+        }
+        view(&mut out, object)?;
+    }
+    if let Some(formatter) = disassembly {
+        if !views.is_empty() {
+            writeln!(out)?;
+        }
+        display::write_disassembly(&mut out, object, formatter)?;
+    }
+    Ok(())
+}
+
+fn dump(matches: &ArgMatches) -> ExitCode {
+    let file_name = matches.get_one::<String>("file").expect("File is required");
+    let segment = match matches.get_one::<String>("segment").map(String::as_str) {
+        Some("data") => SegmentType::Data,
+        _ => SegmentType::Text,
+    };
+    let object = match open_object(file_name) {
+        Ok(object) => object,
+        Err(code) => return code,
+    };
+    if let Err(err) = display::write_dump(&mut io::stdout().lock(), &object, segment) {
+        eprintln!("wobj: {}", err);
+        return ExitCode::from(EXIT_OUTPUT);
+    }
+    ExitCode::SUCCESS
+}
+
+fn size(matches: &ArgMatches) -> ExitCode {
+    let mut file_names = Vec::new();
+    let mut status = ExitCode::SUCCESS;
+    for pattern in matches.get_many::<String>("files").into_iter().flatten() {
+        match expand_pattern(pattern) {
+            Ok(paths) => file_names.extend(paths),
+            Err(err) => {
+                eprintln!("wobj: {}: {}", pattern, err);
+                status = ExitCode::from(EXIT_UNREADABLE);
+            }
+        }
+    }
+
+    let mut rows = Vec::new();
+    for file_name in file_names {
+        match open_object(&file_name) {
+            Ok(object) => rows.push((file_name, SizeSummary::from_object(&object))),
+            Err(code) => status = code,
+        }
+    }
+    if let Err(err) = print_size_table(&rows) {
+        eprintln!("wobj: {}", err);
+        return ExitCode::from(EXIT_OUTPUT);
+    }
+    status
+}
+
+fn wasm(matches: &ArgMatches) -> ExitCode {
+    let file_name = matches.get_one::<String>("file").expect("File is required");
+    let output = match matches.get_one::<String>("output") {
+        Some(output) => output.into(),
+        None => Path::new(file_name).with_extension("o"),
+    };
+    let isa = match load_isa(matches) {
+        Ok(isa) => isa,
+        Err(code) => return code,
+    };
+    let source = match std::fs::read_to_string(file_name) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("wobj: {}: {}", file_name, err);
+            return ExitCode::from(EXIT_UNREADABLE);
+        }
+    };
+
+    let object = match asm::assemble_named(Path::new(file_name), &source, &isa) {
+        Ok(object) => object,
+        Err(errors) => {
+            for err in &errors {
+                eprintln!("wobj: {}", err);
+            }
+            return ExitCode::from(EXIT_CORRUPT);
+        }
+    };
+    if let Err(err) = std::fs::write(&output, object.to_bytes()) {
+        eprintln!("wobj: {}: {}", output.display(), err);
+        return ExitCode::from(EXIT_OUTPUT);
+    }
+    ExitCode::SUCCESS
+}
+
+/// Prints one row per file, plus a totals row when there is more than one.
+fn print_size_table(rows: &[(String, SizeSummary)]) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    let mut totals = SizeSummary::default();
+    display::write_size_heading(&mut out)?;
+    for (file_name, summary) in rows {
+        totals.add(summary);
+        display::write_size_row(&mut out, summary, file_name)?;
+    }
+    if rows.len() > 1 {
+        display::write_size_row(&mut out, &totals, "(TOTALS)")?;
     }
     Ok(())
 }
+
+/// Expands a glob pattern to the files it matches. Names without glob
+/// metacharacters are passed through untouched so that a missing file is
+/// still reported as missing.
+fn expand_pattern(pattern: &str) -> Result<Vec<String>, String> {
+    if !pattern.contains(['*', '?', '[']) {
+        return Ok(vec![pattern.to_string()]);
+    }
+    let paths = glob::glob(pattern).map_err(|err| err.to_string())?;
+    let names: Vec<String> = paths
+        .filter_map(Result::ok)
+        .map(|path| path.display().to_string())
+        .collect();
+    if names.is_empty() {
+        return Err("no files match".to_string());
+    }
+    Ok(names)
+}
+
+fn isa_arg() -> Arg {
+    Arg::new("isa")
+        .long("isa")
+        .value_name("FILE")
+        .help("Layer an ISA variant description over the built-in WRAMP tables")
+}
+
+/// The built-in ISA, or the variant named by `--isa` layered over it.
+fn load_isa(matches: &ArgMatches) -> Result<Arc<Isa>, ExitCode> {
+    match matches.get_one::<String>("isa") {
+        Some(path) => match Isa::builtin().load_variant(Path::new(path)) {
+            Ok(isa) => Ok(Arc::new(isa)),
+            Err(err) => {
+                eprintln!("wobj: {}: {}", path, err);
+                Err(ExitCode::from(match err {
+                    IsaError::Io(_) => EXIT_UNREADABLE,
+                    IsaError::Invalid(_) => EXIT_CORRUPT,
+                }))
+            }
+        },
+        None => Ok(Isa::builtin()),
+    }
+}
+
+/// Loads `file_name`, reporting any failure and turning it into the exit
+/// status that tells a corrupt object apart from an unreadable file.
+fn open_object(file_name: &str) -> Result<ObjectFile, ExitCode> {
+    load_object(file_name).map_err(|err| {
+        eprintln!("wobj: {}: {}", file_name, err);
+        ExitCode::from(if err.is_corrupt() {
+            EXIT_CORRUPT
+        } else {
+            EXIT_UNREADABLE
+        })
+    })
+}
+
+fn load_object(file_name: &str) -> Result<ObjectFile, ObjError> {
+    let file = File::open(file_name)?;
+    ObjectFile::from_reader(&mut io::BufReader::new(file))
+}
@@ -0,0 +1,538 @@
+use crate::error::ObjError;
+use byteorder::ByteOrder;
+use byteorder::LittleEndian;
+use std::io;
+use std::io::Read;
+
+pub const SEG_TYPE_NAME: [&str; 5] = ["NONE", "TEXT", "DATA", "BSS", "NUM_SEGMENTS"];
+/// Indexed by the on-disk reference type code.
+pub const REF_TYPE_NAME: [&str; 7] = [
+    "GLOBAL_DATA",
+    "GLOBAL_TEXT",
+    "GLOBAL_BSS",
+    "TEXT_LABEL_REF",
+    "DATA_LABEL_REF",
+    "BSS_LABEL_REF",
+    "EXTERNAL_REF",
+];
+pub const TEXT: usize = 1;
+pub const DATA: usize = 2;
+pub const BSS: usize = 3;
+
+pub const MAGIC_NUMBER: u32 = 0xdaa1;
+pub const HEADER_SIZE: usize = 24;
+/// address (u32), symbol_ptr (u32), reference type (u8), segment type (u8)
+pub const RELOC_ENTRY_SIZE: usize = 10;
+/// The most words a segment can hold: everything the 20-bit address field
+/// can reach. Keeping each segment this small means segment addresses fit
+/// in a `u32`.
+pub const MAX_SEGMENT_SIZE: u32 = 0x10_0000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ObjectHeader {
+    pub magic_number: u32,
+    pub text_seg_size: u32,
+    pub data_seg_size: u32,
+    pub bss_seg_size: u32,
+    pub num_references: u32,
+    pub symbol_name_table_size: u32,
+}
+
+impl ObjectHeader {
+    pub fn from_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut buffer = [0u8; HEADER_SIZE];
+        reader.read_exact(&mut buffer)?;
+        Ok(Self {
+            magic_number: LittleEndian::read_u32(&buffer[0..4]),
+            text_seg_size: LittleEndian::read_u32(&buffer[4..8]),
+            data_seg_size: LittleEndian::read_u32(&buffer[8..12]),
+            bss_seg_size: LittleEndian::read_u32(&buffer[12..16]),
+            num_references: LittleEndian::read_u32(&buffer[16..20]),
+            symbol_name_table_size: LittleEndian::read_u32(&buffer[20..24]),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReferenceType {
+    GlobalData,
+    GlobalText,
+    GlobalBss,
+    TextLabelRef,
+    DataLabelRef,
+    BssLabelRef,
+    ExternalRef,
+}
+
+impl ReferenceType {
+    /// Maps an on-disk reference type code to its `ReferenceType`.
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(ReferenceType::GlobalData),
+            1 => Some(ReferenceType::GlobalText),
+            2 => Some(ReferenceType::GlobalBss),
+            3 => Some(ReferenceType::TextLabelRef),
+            4 => Some(ReferenceType::DataLabelRef),
+            5 => Some(ReferenceType::BssLabelRef),
+            6 => Some(ReferenceType::ExternalRef),
+            _ => None,
+        }
+    }
+
+    /// The on-disk code for this reference type; the inverse of `from_code`.
+    pub fn code(self) -> u8 {
+        match self {
+            ReferenceType::GlobalData => 0,
+            ReferenceType::GlobalText => 1,
+            ReferenceType::GlobalBss => 2,
+            ReferenceType::TextLabelRef => 3,
+            ReferenceType::DataLabelRef => 4,
+            ReferenceType::BssLabelRef => 5,
+            ReferenceType::ExternalRef => 6,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        REF_TYPE_NAME[self.code() as usize]
+    }
+
+    /// True for the entries that define a global label rather than refer
+    /// to one.
+    pub fn is_global(self) -> bool {
+        matches!(
+            self,
+            ReferenceType::GlobalData | ReferenceType::GlobalText | ReferenceType::GlobalBss
+        )
+    }
+
+    /// True for the entries whose `symbol_ptr` names a symbol.
+    pub fn has_symbol(self) -> bool {
+        self.is_global() || self == ReferenceType::ExternalRef
+    }
+
+    /// The segment a global label lives in, or the segment a local label
+    /// reference points into. External references have no known segment.
+    pub fn target_segment(self) -> Option<SegmentType> {
+        match self {
+            ReferenceType::GlobalText | ReferenceType::TextLabelRef => Some(SegmentType::Text),
+            ReferenceType::GlobalData | ReferenceType::DataLabelRef => Some(SegmentType::Data),
+            ReferenceType::GlobalBss | ReferenceType::BssLabelRef => Some(SegmentType::Bss),
+            ReferenceType::ExternalRef => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SegmentType {
+    Text,
+    Data,
+    Bss,
+    NumSegments,
+}
+
+impl SegmentType {
+    /// Maps an on-disk segment code (an index into `SEG_TYPE_NAME`) to its
+    /// `SegmentType`. `NONE` decodes as `Ok(None)`; anything outside the
+    /// real segments is an error.
+    pub fn from_code(code: u8) -> Result<Option<Self>, u8> {
+        match code as usize {
+            0 => Ok(None),
+            TEXT => Ok(Some(SegmentType::Text)),
+            DATA => Ok(Some(SegmentType::Data)),
+            BSS => Ok(Some(SegmentType::Bss)),
+            _ => Err(code),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        SEG_TYPE_NAME[Self::code(Some(self)) as usize]
+    }
+
+    /// The on-disk code for an optional segment; the inverse of `from_code`.
+    pub fn code(seg: Option<Self>) -> u8 {
+        let index = match seg {
+            None => 0,
+            Some(SegmentType::Text) => TEXT,
+            Some(SegmentType::Data) => DATA,
+            Some(SegmentType::Bss) => BSS,
+            Some(SegmentType::NumSegments) => SEG_TYPE_NAME.len() - 1,
+        };
+        index as u8
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RelocEntry {
+    pub address: u32,
+    pub symbol_ptr: u32,
+    pub ref_type: ReferenceType,
+    pub seg_type: Option<SegmentType>,
+    /// The name `symbol_ptr` resolves to, for global and external entries.
+    pub symbol_name: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LabelEntry {
+    pub name: String,
+    pub address: u32,
+    /// `None` for unresolved external symbols.
+    pub seg_type: Option<SegmentType>,
+    pub resolved: bool,
+    pub is_global: bool,
+}
+
+/// An owned, fully loaded WRAMP object file.
+///
+/// Segment sizes in the header are counted in words, and every address in
+/// the object is a word address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjectFile {
+    pub header: ObjectHeader,
+    pub text: Vec<u32>,
+    pub data: Vec<u32>,
+    pub bss_size: u32,
+    pub reloc_entries: Vec<RelocEntry>,
+    pub label_entries: Vec<LabelEntry>,
+    pub symbol_table: SymbolTable,
+}
+
+impl ObjectFile {
+    pub fn from_reader<R: Read>(reader: &mut R) -> Result<Self, ObjError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Self::parse(&bytes)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, ObjError> {
+        let header =
+            ObjectHeader::from_reader(&mut &bytes[..]).map_err(|_| ObjError::TruncatedHeader {
+                offset: bytes.len(),
+            })?;
+        if header.magic_number != MAGIC_NUMBER {
+            return Err(ObjError::BadMagic {
+                offset: 0,
+                found: header.magic_number,
+            });
+        }
+        for (section, offset, size) in [
+            ("text segment", 4, header.text_seg_size),
+            ("data segment", 8, header.data_seg_size),
+            ("bss segment", 12, header.bss_seg_size),
+        ] {
+            if size > MAX_SEGMENT_SIZE {
+                return Err(ObjError::SegmentTooLarge {
+                    section,
+                    offset,
+                    size,
+                });
+            }
+        }
+
+        let mut cursor = HEADER_SIZE;
+        let (_, text_bytes) = section(bytes, &mut cursor, "text segment", header.text_seg_size, 4)?;
+        let (_, data_bytes) = section(bytes, &mut cursor, "data segment", header.data_seg_size, 4)?;
+        let (reloc_offset, reloc_bytes) = section(
+            bytes,
+            &mut cursor,
+            "relocation table",
+            header.num_references,
+            RELOC_ENTRY_SIZE,
+        )?;
+        let (table_offset, table_bytes) = section(
+            bytes,
+            &mut cursor,
+            "symbol name table",
+            header.symbol_name_table_size,
+            1,
+        )?;
+        if cursor < bytes.len() {
+            return Err(ObjError::TrailingGarbage {
+                offset: cursor,
+                len: bytes.len() - cursor,
+            });
+        }
+
+        let mut object = Self {
+            header,
+            text: read_words(text_bytes),
+            data: read_words(data_bytes),
+            bss_size: header.bss_seg_size,
+            reloc_entries: Vec::new(),
+            label_entries: Vec::new(),
+            symbol_table: SymbolTable::parse(table_bytes, table_offset)?,
+        };
+
+        for (index, chunk) in reloc_bytes.chunks_exact(RELOC_ENTRY_SIZE).enumerate() {
+            let index = index as u32;
+            let offset = reloc_offset + index as usize * RELOC_ENTRY_SIZE;
+            let mut entry = read_reloc_entry(chunk, index, offset)?;
+            object.check_reloc_range(&entry, index, offset)?;
+            if entry.ref_type.has_symbol() {
+                let name = object.symbol_table.name_at(entry.symbol_ptr).ok_or(
+                    ObjError::BadSymbolPointer {
+                        index,
+                        offset: offset + 4,
+                        ptr: entry.symbol_ptr,
+                        table_size: object.symbol_table.len(),
+                    },
+                )?;
+                entry.symbol_name = Some(name.to_string());
+            }
+            object.reloc_entries.push(entry);
+        }
+        object.label_entries = collect_labels(&object.reloc_entries);
+
+        Ok(object)
+    }
+
+    /// Builds an object from its parts, deriving the header and resolving
+    /// symbol names the same way `parse` does. Entries whose `symbol_ptr`
+    /// does not resolve are left without a name.
+    pub fn from_parts(
+        text: Vec<u32>,
+        data: Vec<u32>,
+        bss_size: u32,
+        mut reloc_entries: Vec<RelocEntry>,
+        symbol_table: SymbolTable,
+    ) -> Self {
+        for entry in &mut reloc_entries {
+            entry.symbol_name = if entry.ref_type.has_symbol() {
+                symbol_table.name_at(entry.symbol_ptr).map(str::to_string)
+            } else {
+                None
+            };
+        }
+        let header = ObjectHeader {
+            magic_number: MAGIC_NUMBER,
+            text_seg_size: text.len() as u32,
+            data_seg_size: data.len() as u32,
+            bss_seg_size: bss_size,
+            num_references: reloc_entries.len() as u32,
+            symbol_name_table_size: symbol_table.len() as u32,
+        };
+        Self {
+            header,
+            text,
+            data,
+            bss_size,
+            label_entries: collect_labels(&reloc_entries),
+            reloc_entries,
+            symbol_table,
+        }
+    }
+
+    /// Starting word address of `seg` when the segments are laid out
+    /// text, data, bss from address zero. `NumSegments` gives the end of
+    /// the image.
+    pub fn segment_address(&self, seg: SegmentType) -> u32 {
+        let text_size = self.text.len() as u32;
+        let data_size = self.data.len() as u32;
+        match seg {
+            SegmentType::Text => 0,
+            SegmentType::Data => text_size,
+            SegmentType::Bss => text_size + data_size,
+            SegmentType::NumSegments => text_size + data_size + self.bss_size,
+        }
+    }
+
+    /// Size of `seg` in words.
+    pub fn segment_size(&self, seg: SegmentType) -> u32 {
+        match seg {
+            SegmentType::Text => self.text.len() as u32,
+            SegmentType::Data => self.data.len() as u32,
+            SegmentType::Bss => self.bss_size,
+            SegmentType::NumSegments => 0,
+        }
+    }
+
+    /// File offset of relocation entry `index`.
+    pub fn reloc_entry_offset(&self, index: usize) -> usize {
+        HEADER_SIZE + 4 * (self.text.len() + self.data.len()) + index * RELOC_ENTRY_SIZE
+    }
+
+    /// References must patch a word inside their segment; a global label
+    /// may also sit one past the end of its segment.
+    fn check_reloc_range(
+        &self,
+        entry: &RelocEntry,
+        index: u32,
+        offset: usize,
+    ) -> Result<(), ObjError> {
+        let (segment, in_range) = if entry.ref_type.is_global() {
+            let Some(segment) = entry.ref_type.target_segment() else {
+                return Ok(());
+            };
+            (segment, entry.address <= self.segment_size(segment))
+        } else {
+            let Some(segment) = entry.seg_type else {
+                return Ok(());
+            };
+            (segment, entry.address < self.segment_size(segment))
+        };
+        if in_range {
+            Ok(())
+        } else {
+            Err(ObjError::RelocOutOfRange {
+                index,
+                offset,
+                address: entry.address,
+                segment,
+                size: self.segment_size(segment),
+            })
+        }
+    }
+}
+
+/// Slices the next `count` records of `record_size` bytes off `bytes` at
+/// `cursor`, returning the section's starting offset.
+fn section<'a>(
+    bytes: &'a [u8],
+    cursor: &mut usize,
+    name: &'static str,
+    count: u32,
+    record_size: usize,
+) -> Result<(usize, &'a [u8]), ObjError> {
+    let offset = *cursor;
+    let size = count as u64 * record_size as u64;
+    if offset as u64 + size > bytes.len() as u64 {
+        return Err(ObjError::SegmentPastEof {
+            section: name,
+            offset,
+            size,
+            file_len: bytes.len(),
+        });
+    }
+    *cursor += size as usize;
+    Ok((offset, &bytes[offset..*cursor]))
+}
+
+fn read_words(bytes: &[u8]) -> Vec<u32> {
+    bytes.chunks_exact(4).map(LittleEndian::read_u32).collect()
+}
+
+fn read_reloc_entry(bytes: &[u8], index: u32, offset: usize) -> Result<RelocEntry, ObjError> {
+    let ref_code = bytes[8];
+    let seg_code = bytes[9];
+    let ref_type = ReferenceType::from_code(ref_code).ok_or(ObjError::BadRefType {
+        index,
+        offset: offset + 8,
+        code: ref_code,
+    })?;
+    let seg_type = SegmentType::from_code(seg_code).map_err(|code| ObjError::BadSegType {
+        index,
+        offset: offset + 9,
+        code,
+    })?;
+
+    Ok(RelocEntry {
+        address: LittleEndian::read_u32(&bytes[0..4]),
+        symbol_ptr: LittleEndian::read_u32(&bytes[4..8]),
+        ref_type,
+        seg_type,
+        symbol_name: None,
+    })
+}
+
+/// Builds the label list from the global definitions and the (deduplicated)
+/// external references in the relocation table.
+fn collect_labels(reloc_entries: &[RelocEntry]) -> Vec<LabelEntry> {
+    let mut labels: Vec<LabelEntry> = Vec::new();
+    for entry in reloc_entries {
+        let Some(name) = &entry.symbol_name else {
+            continue;
+        };
+        if entry.ref_type.is_global() {
+            labels.push(LabelEntry {
+                name: name.clone(),
+                address: entry.address,
+                seg_type: entry.ref_type.target_segment(),
+                resolved: true,
+                is_global: true,
+            });
+        } else if !labels
+            .iter()
+            .any(|label| !label.resolved && label.name == *name)
+        {
+            labels.push(LabelEntry {
+                name: name.clone(),
+                address: 0,
+                seg_type: None,
+                resolved: false,
+                is_global: false,
+            });
+        }
+    }
+    labels
+}
+
+/// The symbol name table: NUL-terminated names addressed by byte offset.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    bytes: Vec<u8>,
+}
+
+impl SymbolTable {
+    /// Checks a raw name table found at file offset `base`: every name must
+    /// be UTF-8 and NUL-terminated.
+    pub fn parse(bytes: &[u8], base: usize) -> Result<Self, ObjError> {
+        if let Err(err) = std::str::from_utf8(bytes) {
+            return Err(ObjError::BadSymbolTable {
+                offset: base + err.valid_up_to(),
+                reason: "name is not valid UTF-8",
+            });
+        }
+        if bytes.last().is_some_and(|&last| last != 0) {
+            return Err(ObjError::BadSymbolTable {
+                offset: base + bytes.iter().rposition(|&b| b == 0).map_or(0, |pos| pos + 1),
+                reason: "missing NUL terminator",
+            });
+        }
+        Ok(Self {
+            bytes: bytes.to_vec(),
+        })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Returns the offset of `name`, appending it to the table if it is not
+    /// already there. `name` must not contain a NUL byte.
+    pub fn intern(&mut self, name: &str) -> u32 {
+        if let Some((ptr, _)) = self.entries().find(|&(_, entry)| entry == name) {
+            return ptr;
+        }
+        let ptr = self.bytes.len() as u32;
+        self.bytes.extend_from_slice(name.as_bytes());
+        self.bytes.push(0);
+        ptr
+    }
+
+    /// The name starting at byte offset `ptr`, if `ptr` is inside the table.
+    pub fn name_at(&self, ptr: u32) -> Option<&str> {
+        let start = ptr as usize;
+        let rest = self.bytes.get(start..).filter(|rest| !rest.is_empty())?;
+        // `parse` guarantees a terminator somewhere after `start`.
+        let len = rest.iter().position(|&b| b == 0)?;
+        std::str::from_utf8(&rest[..len]).ok()
+    }
+
+    /// Every name in the table with the offset it starts at.
+    pub fn entries(&self) -> impl Iterator<Item = (u32, &str)> {
+        let mut offset = 0u32;
+        self.bytes.split_inclusive(|&b| b == 0).map(move |chunk| {
+            let start = offset;
+            offset += chunk.len() as u32;
+            let name = std::str::from_utf8(&chunk[..chunk.len() - 1]).unwrap_or("");
+            (start, name)
+        })
+    }
+}
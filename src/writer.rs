@@ -0,0 +1,52 @@
+use crate::object::{ObjectFile, ObjectHeader, SegmentType};
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::io;
+use std::io::Write;
+
+impl ObjectHeader {
+    /// Writes the header in the layout `from_reader` consumes.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_u32::<LittleEndian>(self.magic_number)?;
+        writer.write_u32::<LittleEndian>(self.text_seg_size)?;
+        writer.write_u32::<LittleEndian>(self.data_seg_size)?;
+        writer.write_u32::<LittleEndian>(self.bss_seg_size)?;
+        writer.write_u32::<LittleEndian>(self.num_references)?;
+        writer.write_u32::<LittleEndian>(self.symbol_name_table_size)
+    }
+}
+
+impl ObjectFile {
+    /// Serializes the object in the exact layout `parse` reads: header,
+    /// text, data, relocation entries, then the symbol name table.
+    ///
+    /// The header's counts are taken from the contents rather than from
+    /// `self.header`, so an object edited in memory is written consistently.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let header = ObjectHeader {
+            magic_number: self.header.magic_number,
+            text_seg_size: self.text.len() as u32,
+            data_seg_size: self.data.len() as u32,
+            bss_seg_size: self.bss_size,
+            num_references: self.reloc_entries.len() as u32,
+            symbol_name_table_size: self.symbol_table.len() as u32,
+        };
+        header.write_to(writer)?;
+        for &word in self.text.iter().chain(&self.data) {
+            writer.write_u32::<LittleEndian>(word)?;
+        }
+        for entry in &self.reloc_entries {
+            writer.write_u32::<LittleEndian>(entry.address)?;
+            writer.write_u32::<LittleEndian>(entry.symbol_ptr)?;
+            writer.write_u8(entry.ref_type.code())?;
+            writer.write_u8(SegmentType::code(entry.seg_type))?;
+        }
+        writer.write_all(self.symbol_table.as_bytes())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_to(&mut bytes)
+            .expect("writing to a Vec cannot fail");
+        bytes
+    }
+}
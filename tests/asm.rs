@@ -0,0 +1,479 @@
+use rwobj::asm::{assemble, assemble_named, AsmError, Expansion};
+use rwobj::display::write_disassembly;
+use rwobj::format::{FormatOptions, Formatter};
+use rwobj::isa::Isa;
+use rwobj::{ObjectFile, ReferenceType, SegmentType};
+use std::fs;
+use std::path::Path;
+
+fn assemble_ok(source: &str) -> ObjectFile {
+    assemble(source, &Isa::builtin()).unwrap_or_else(|errors| {
+        panic!(
+            "{}",
+            errors
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("\n")
+        )
+    })
+}
+
+/// `(line, message)` for every error in `source`.
+fn errors(source: &str) -> Vec<(usize, String)> {
+    match assemble(source, &Isa::builtin()) {
+        Ok(_) => panic!("{:?} assembled", source),
+        Err(errors) => errors
+            .into_iter()
+            .map(|err| (err.line, err.message))
+            .collect(),
+    }
+}
+
+#[test]
+fn disassembled_fixtures_reassemble_byte_for_byte() {
+    let formatter = Formatter::new(FormatOptions {
+        show_raw: false,
+        ..FormatOptions::default()
+    });
+    for name in ["hello.o", "loop.o", "empty.o"] {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name);
+        let bytes = fs::read(path).unwrap();
+        let mut source = Vec::new();
+        write_disassembly(&mut source, &ObjectFile::parse(&bytes).unwrap(), &formatter).unwrap();
+        let object = assemble_ok(&String::from_utf8(source).unwrap());
+        assert_eq!(object.to_bytes(), bytes, "{} did not reassemble", name);
+    }
+
+    for source in [
+        // Fixed addresses have no relocation and must stay numbers.
+        "la $2, 2\nj 1\njr $ra",
+        // A negative displacement from $0 is not an address.
+        "lw $1, -1($0)\nsw $2, 4($0)",
+        // Aliased globals each get a label line.
+        ".global a, b\na:\nb: jr $ra\n.data\n.global c, d\nc:\nd: .word a",
+        // A branch before address 0.
+        "beqz $1, -4\nbnez $2, 0x10",
+        // References past the end of their segment keep their relocation.
+        "la $1, buf + 10\n.data\ns: .word 1\n.word s + 100\n.bss\nbuf: .space 2",
+    ] {
+        let object = assemble_ok(source);
+        let mut disassembly = Vec::new();
+        write_disassembly(&mut disassembly, &object, &formatter).unwrap();
+        let disassembly = String::from_utf8(disassembly).unwrap();
+        assert_eq!(
+            assemble_ok(&disassembly).to_bytes(),
+            object.to_bytes(),
+            "{}",
+            disassembly
+        );
+    }
+    assert!(assemble_ok("la $2, 2\nj 1\njr $ra")
+        .reloc_entries
+        .is_empty());
+
+    // Branches out of the text segment need no label.
+    let mut disassembly = Vec::new();
+    let object = assemble_ok("beqz $1, -4\nbnez $2, 0x10");
+    write_disassembly(&mut disassembly, &object, &formatter).unwrap();
+    let disassembly = String::from_utf8(disassembly).unwrap();
+    assert!(!disassembly.contains("L_"), "{}", disassembly);
+}
+
+#[test]
+fn directives_lay_out_segments_and_relocations() {
+    let object = assemble_ok(
+        r#"
+        .equ    COUNT, 3
+        .extern handler
+        .global start, table
+        .text
+start:  addi    $1, $0, COUNT       # comment
+        lw      $2, table($0)
+        sw      $2, -1($sp)
+        la      $3, scratch
+        jal     handler
+        beqz    $1, start
+        movgs   $evec, $3
+        .data
+msg:    .ascii  "ok", "x"
+table:  .word   start, msg, handler, -2
+        .space  COUNT
+        .bss
+scratch: .space 8
+        "#,
+    );
+    assert!(object.check().is_empty(), "{:?}", object.check());
+    assert_eq!(object.text.len(), 7);
+    assert_eq!(object.text[0], 0x1100_0003);
+    assert_eq!(object.text[1], 0x8200_0000 | 3);
+    assert_eq!(object.text[2], 0x92e0_0000 | 0xfffff);
+    assert_eq!(object.text[5] & 0xfffff, 0xffffa);
+    let data: Vec<u32> = "okx".bytes().map(u32::from).collect();
+    assert_eq!(object.data[..3], data);
+    assert_eq!(object.data[3..], [0, 0, 0, 0xffff_fffe, 0, 0, 0]);
+    assert_eq!(object.bss_size, 8);
+
+    let relocs: Vec<_> = object
+        .reloc_entries
+        .iter()
+        .map(|entry| {
+            (
+                entry.ref_type,
+                entry.seg_type,
+                entry.address,
+                entry.symbol_name.as_deref(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        relocs,
+        [
+            (ReferenceType::GlobalText, None, 0, Some("start")),
+            (
+                ReferenceType::DataLabelRef,
+                Some(SegmentType::Text),
+                1,
+                None
+            ),
+            (ReferenceType::BssLabelRef, Some(SegmentType::Text), 3, None),
+            (
+                ReferenceType::ExternalRef,
+                Some(SegmentType::Text),
+                4,
+                Some("handler")
+            ),
+            (ReferenceType::GlobalData, None, 3, Some("table")),
+            (
+                ReferenceType::TextLabelRef,
+                Some(SegmentType::Data),
+                3,
+                None
+            ),
+            (
+                ReferenceType::DataLabelRef,
+                Some(SegmentType::Data),
+                4,
+                None
+            ),
+            (
+                ReferenceType::ExternalRef,
+                Some(SegmentType::Data),
+                5,
+                Some("handler")
+            ),
+        ]
+    );
+}
+
+#[test]
+fn variant_register_names_are_accepted() {
+    let isa = Isa::builtin()
+        .with_variant("lab", "[registers]\ngpr = { 12 = \"$fp\" }")
+        .unwrap();
+    let object = assemble("add $fp, $12, $sp", &isa).unwrap();
+    assert_eq!(object.text, [0x0cc0_000e]);
+}
+
+#[test]
+fn every_error_is_reported_with_its_line() {
+    let source = [
+        "        add $1, $2",
+        "start:  addi $1, $0, 0x10000",
+        "start:  nop",
+        "        j nowhere",
+        "        .data",
+        "        lw $1, 0($2)",
+        "        bogus $1",
+        "        .global missing",
+        "        .bss",
+        "        .word 1",
+        "        movgs $1, $2",
+    ];
+    let found = errors(&source.join("\n"));
+    let expected = [
+        (1, "add: expected 3 operands, found 2"),
+        (2, "addi: immediate 65536 does not fit in 16 bits"),
+        (3, "`start` is already defined"),
+        (4, "j: undefined symbol `nowhere`"),
+        (6, "instruction `lw` outside the text segment"),
+        (7, "unknown instruction `bogus`"),
+        (8, "global `missing` is never defined"),
+        (10, ".word in the bss segment, which only takes .space"),
+        (11, "instruction `movgs` outside the text segment"),
+    ];
+    let found: Vec<(usize, &str)> = found
+        .iter()
+        .map(|(line, message)| (*line, message.as_str()))
+        .collect();
+    assert_eq!(found, expected);
+}
+
+#[test]
+fn expressions_fold_constants_and_keep_relocations() {
+    let object = assemble_ok(
+        r#"
+        .equ    SIZE, end - start
+        .equ    MASK, (1 << 4) - 1 | 0x100
+        .equ    BIG, 0x12345678
+        .extern table
+start:  addi    $1, $0, SIZE
+        andi    $2, $1, MASK ^ 3
+        lhi     $3, %hi(BIG)
+        ori     $3, $3, %lo(BIG)
+        addi    $4, $0, -3 * 4 + 20 / 3 % 4
+        lw      $5, data_end - 1($0)
+        la      $6, table + 2
+        j       start + 1
+end:
+        .data
+values: .word   ~0, 7 >> 1, end - start + 1, values + 1
+data_end:
+        "#,
+    );
+    assert!(object.check().is_empty(), "{:?}", object.check());
+    let immediates: Vec<u32> = object.text[..5].iter().map(|word| word & 0xffff).collect();
+    assert_eq!(immediates, [8, 0x10c, 0x1234, 0x5678, 0xfff6]);
+    let fields: Vec<u32> = object.text[5..].iter().map(|word| word & 0xfffff).collect();
+    assert_eq!(fields, [3, 2, 1]);
+    assert_eq!(object.data, [0xffff_ffff, 3, 9, 1]);
+
+    let relocs: Vec<_> = object
+        .reloc_entries
+        .iter()
+        .map(|entry| (entry.ref_type, entry.address, entry.symbol_name.as_deref()))
+        .collect();
+    assert_eq!(
+        relocs,
+        [
+            (ReferenceType::DataLabelRef, 5, None),
+            (ReferenceType::ExternalRef, 6, Some("table")),
+            (ReferenceType::TextLabelRef, 7, None),
+            (ReferenceType::DataLabelRef, 3, None),
+        ]
+    );
+}
+
+#[test]
+fn expressions_that_cannot_be_relocated_are_rejected() {
+    let source = [
+        "start:  addi $1, $0, %hi(start)",
+        "        addi $1, $0, start",
+        "        .equ A, B + 1",
+        "        .equ B, A",
+        "        la $1, start * 2",
+        "        .data",
+        "msg:    .word start - msg",
+        "        .word 1 / 0",
+        "        .word (1 + 2",
+    ];
+    let found = errors(&source.join("\n"));
+    let expected = [
+        (1, "addi: `%hi` of a relocatable value cannot be relocated"),
+        (
+            2,
+            "addi: a 16-bit immediate cannot be relocated; use la for addresses",
+        ),
+        (3, "`A` is defined in terms of itself"),
+        (4, "`B` is defined in terms of itself"),
+        (5, "la: `*` of a relocatable value cannot be relocated"),
+        (7, "cannot subtract a data label from a text label"),
+        (8, "division by zero"),
+        (9, "expected `)`"),
+    ];
+    let found: Vec<(usize, &str)> = found
+        .iter()
+        .map(|(line, message)| (*line, message.as_str()))
+        .collect();
+    assert_eq!(found, expected);
+}
+
+#[test]
+fn macros_expand_in_place_with_their_own_local_labels() {
+    let with_macros = assemble_ok(
+        r#"
+        .equ    DEBUG, 0
+        .macro  enter frame
+        subui   $sp, $sp, \frame
+        sw      $ra, \frame - 1($sp)
+        .endm
+        .macro  leave frame
+        lw      $ra, \frame - 1($sp)
+        addui   $sp, $sp, \frame
+        jr      $ra
+        .endm
+        .macro  countdown reg, from
+        addi    \reg, $0, \from
+loop\@: subi    \reg, \reg, 1
+        bnez    \reg, loop\@
+        .endm
+main:   enter   2
+        countdown $1, 10
+        countdown $2, 'a'
+        .if     DEBUG
+        syscall
+        .else
+        .if     DEBUG + 1
+        add     $3, $0, $0
+        .endif
+        .endif
+        leave   2
+        "#,
+    );
+    let by_hand = assemble_ok(
+        r#"
+main:   subui   $sp, $sp, 2
+        sw      $ra, 1($sp)
+        addi    $1, $0, 10
+first:  subi    $1, $1, 1
+        bnez    $1, first
+        addi    $2, $0, 97
+second: subi    $2, $2, 1
+        bnez    $2, second
+        add     $3, $0, $0
+        lw      $ra, 1($sp)
+        addui   $sp, $sp, 2
+        jr      $ra
+        "#,
+    );
+    assert_eq!(with_macros.to_bytes(), by_hand.to_bytes());
+}
+
+#[test]
+fn included_macros_report_their_definition_and_call_site() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("asm_include");
+    fs::create_dir_all(&dir).unwrap();
+    let macros = dir.join("macros.s");
+    let lines = [
+        "        .macro  push reg",
+        "        subui   $sp, $sp, 1",
+        "        sw      \\reg, 0($sp)",
+        "        .endm",
+    ];
+    fs::write(&macros, lines.join("\n")).unwrap();
+    let main = dir.join("main.s");
+    let isa = Isa::builtin();
+
+    let source = "        .include \"macros.s\"\n        push $ra\n";
+    let object = assemble_named(&main, source, &isa).unwrap();
+    assert_eq!(
+        object.to_bytes(),
+        assemble_ok("subui $sp, $sp, 1\nsw $ra, 0($sp)").to_bytes()
+    );
+
+    let source = "        .include \"macros.s\"\n        push $ra\n        push $nope\n";
+    let errors = assemble_named(&main, source, &isa).unwrap_err();
+    assert_eq!(
+        errors,
+        [AsmError {
+            file: Some(macros.clone()),
+            line: 3,
+            message: "sw: `$nope` is not a general purpose register".to_string(),
+            expansions: vec![Expansion {
+                name: "push".to_string(),
+                file: Some(main.clone()),
+                line: 3,
+            }],
+        }]
+    );
+    assert_eq!(
+        errors[0].to_string(),
+        format!(
+            "{}:3: sw: `$nope` is not a general purpose register, in macro `push` called at {}:3",
+            macros.display(),
+            main.display()
+        )
+    );
+}
+
+#[test]
+fn macro_and_conditional_misuse_is_reported() {
+    let source = [
+        "        .if UNDEFINED",
+        "        add $1, $0, $0",
+        "        .endif",
+        "        .else",
+        "        .macro add x",
+        "        .endm",
+        "        .macro twice",
+        "        oops",
+        "        .endm",
+        "        twice",
+        "        twice 1",
+        "        .include \"missing.s\"",
+        "        .if 1",
+    ];
+    let found = assemble(&source.join("\n"), &Isa::builtin()).unwrap_err();
+    let found: Vec<String> = found.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        found[..5],
+        [
+            "line 1: undefined symbol `UNDEFINED`",
+            "line 4: .else without .if",
+            "line 5: cannot define a macro named `add`",
+            "line 8: unknown instruction `oops`, in macro `twice` called at line 10",
+            "line 11: macro `twice` takes 0 arguments, found 1",
+        ]
+    );
+    assert!(found[5].starts_with("line 12: missing.s: "), "{:?}", found);
+    assert_eq!(found[6..], ["line 13: .if has no .endif"]);
+}
+
+#[test]
+fn segments_are_limited_to_the_address_space() {
+    let source = [
+        "        .bss",
+        "a:      .space 0xffffffff",
+        "b:      .space 0x100000",
+        "c:      .space 1",
+        "        .data",
+        "        .space 0x3fffffff",
+        "        .word 1",
+    ];
+    let found = errors(&source.join("\n"));
+    let found: Vec<(usize, &str)> = found
+        .iter()
+        .map(|(line, message)| (*line, message.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            (2, "the bss segment would be larger than 0x100000 words"),
+            (4, "the bss segment would be larger than 0x100000 words"),
+            (6, "the data segment would be larger than 0x100000 words"),
+        ]
+    );
+
+    let object = assemble_ok(".bss\n.space 0x100000");
+    assert_eq!(object.bss_size, 0x100000);
+}
+
+#[test]
+fn space_sizes_must_be_defined_before_use() {
+    let source = [
+        "        .data",
+        "        .space SIZE",
+        "        .space later",
+        "        .space MISSING",
+        "        .equ SIZE, 4",
+        "later:  .word 0",
+    ];
+    let found = errors(&source.join("\n"));
+    let found: Vec<(usize, &str)> = found
+        .iter()
+        .map(|(line, message)| (*line, message.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            (2, ".space size must be defined before the .space"),
+            (3, "expected a constant, not a label"),
+            (4, "undefined symbol `MISSING`"),
+        ]
+    );
+
+    let object = assemble_ok(".equ SIZE, 4\n.data\n.space SIZE\n.word 1");
+    assert_eq!(object.data, [0, 0, 0, 0, 1]);
+}
@@ -0,0 +1,109 @@
+use rwobj::{ObjError, ObjectFile, ReferenceType, RelocEntry, SegmentType, SymbolTable};
+
+fn entry(
+    address: u32,
+    symbol_ptr: u32,
+    ref_type: ReferenceType,
+    seg_type: Option<SegmentType>,
+) -> RelocEntry {
+    RelocEntry {
+        address,
+        symbol_ptr,
+        ref_type,
+        seg_type,
+        symbol_name: None,
+    }
+}
+
+/// Two text words and one data word, so relocation entry `index` starts
+/// at file offset 36 + 10 * index.
+fn object(symbols: SymbolTable, relocs: Vec<RelocEntry>) -> ObjectFile {
+    ObjectFile::from_parts(vec![0, 0], vec![0], 0, relocs, symbols)
+}
+
+#[test]
+fn duplicate_globals_are_reported() {
+    let mut symbols = SymbolTable::default();
+    let main = symbols.intern("main");
+    let object = object(
+        symbols,
+        vec![
+            entry(0, main, ReferenceType::GlobalText, None),
+            entry(1, main, ReferenceType::GlobalText, None),
+        ],
+    );
+    let problems = object.check();
+    assert!(
+        matches!(
+            &problems[..],
+            [ObjError::DuplicateGlobal {
+                index: 1,
+                offset: 46,
+                name,
+                first_index: 0,
+            }] if name == "main"
+        ),
+        "{:?}",
+        problems
+    );
+}
+
+#[test]
+fn symbol_pointers_into_a_name_are_reported() {
+    let mut symbols = SymbolTable::default();
+    let printf = symbols.intern("printf");
+    let object = object(
+        symbols,
+        vec![entry(
+            1,
+            printf + 1,
+            ReferenceType::ExternalRef,
+            Some(SegmentType::Text),
+        )],
+    );
+    let problems = object.check();
+    assert!(
+        matches!(
+            &problems[..],
+            [ObjError::SymbolPointerMidName {
+                index: 0,
+                offset: 40,
+                ptr,
+            }] if *ptr == printf + 1
+        ),
+        "{:?}",
+        problems
+    );
+}
+
+#[test]
+fn references_outside_text_and_data_are_reported() {
+    let object = object(
+        SymbolTable::default(),
+        vec![
+            entry(0, 0, ReferenceType::DataLabelRef, Some(SegmentType::Text)),
+            entry(0, 0, ReferenceType::TextLabelRef, Some(SegmentType::Bss)),
+            entry(0, 0, ReferenceType::TextLabelRef, None),
+        ],
+    );
+    let problems = object.check();
+    assert!(
+        matches!(
+            &problems[..],
+            [
+                ObjError::BadRefSegment {
+                    index: 1,
+                    offset: 55,
+                    segment: Some(SegmentType::Bss),
+                },
+                ObjError::BadRefSegment {
+                    index: 2,
+                    offset: 65,
+                    segment: None,
+                },
+            ]
+        ),
+        "{:?}",
+        problems
+    );
+}
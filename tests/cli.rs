@@ -0,0 +1,117 @@
+use rwobj::{ObjectFile, SymbolTable};
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+fn wobj(args: &[&str]) -> Option<i32> {
+    Command::new(env!("CARGO_BIN_EXE_wobj"))
+        .args(args)
+        .output()
+        .unwrap()
+        .status
+        .code()
+}
+
+#[test]
+fn exit_status_tells_failures_apart() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("cli_exit");
+    fs::create_dir_all(&dir).unwrap();
+    let corrupt = dir.join("corrupt.o");
+    fs::write(&corrupt, b"not an object file, but long enough").unwrap();
+    let missing = dir.join("missing.o");
+    let corrupt = corrupt.to_str().unwrap();
+    let missing = missing.to_str().unwrap();
+    let hello = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/hello.o");
+
+    assert_eq!(wobj(&[hello]), Some(0));
+    assert_eq!(wobj(&[corrupt]), Some(65));
+    assert_eq!(wobj(&[missing]), Some(66));
+    assert_eq!(wobj(&["size", hello, missing]), Some(66));
+    assert_eq!(wobj(&["--no-such-flag", hello]), Some(2));
+
+    let bad_isa = dir.join("bad.toml");
+    fs::write(&bad_isa, "[registers\n").unwrap();
+    let bad_isa = bad_isa.to_str().unwrap();
+    assert_eq!(wobj(&["--isa", bad_isa, hello]), Some(65));
+    assert_eq!(wobj(&["--isa", missing, hello]), Some(66));
+}
+
+#[test]
+fn disassembly_on_its_own_reassembles() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("cli_reassemble");
+    fs::create_dir_all(&dir).unwrap();
+    let hello = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/hello.o");
+    let output = Command::new(env!("CARGO_BIN_EXE_wobj"))
+        .args(["-d", hello])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let source = dir.join("hello.s");
+    let object = dir.join("hello.o");
+    fs::write(&source, output.stdout).unwrap();
+
+    let source = source.to_str().unwrap();
+    let object_path = object.to_str().unwrap();
+    assert_eq!(wobj(&["wasm", source, "-o", object_path]), Some(0));
+    assert_eq!(fs::read(object).unwrap(), fs::read(hello).unwrap());
+}
+
+#[test]
+fn strict_rejects_undecodable_words_in_every_format() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("cli_strict");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("unknown.o");
+    let object = ObjectFile::from_parts(
+        vec![0x0320_0001, 0xf0f0_0000],
+        Vec::new(),
+        0,
+        Vec::new(),
+        SymbolTable::default(),
+    );
+    fs::write(&path, object.to_bytes()).unwrap();
+    let path = path.to_str().unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_wobj"))
+        .args(["-d", path])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let listing = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        listing.contains(".word    0xf0f00000  # unknown encoding: opcode 0xf, func 0x0"),
+        "{}",
+        listing
+    );
+
+    assert_eq!(wobj(&["-d", "--strict", path]), Some(65));
+    assert_eq!(
+        wobj(&["--format", "json", "-d", "--strict", path]),
+        Some(65)
+    );
+    assert_eq!(wobj(&["--strict", path]), Some(2));
+}
+
+#[test]
+fn json_to_a_closed_pipe_is_an_output_error() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("cli_pipe");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("big.o");
+    // Far more JSON than a pipe buffers.
+    let object = ObjectFile::from_parts(
+        Vec::new(),
+        vec![1; 0x10000],
+        0,
+        Vec::new(),
+        SymbolTable::default(),
+    );
+    fs::write(&path, object.to_bytes()).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_wobj"))
+        .args(["--format", "json", path.to_str().unwrap()])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    drop(child.stdout.take());
+    assert_eq!(child.wait().unwrap().code(), Some(74));
+}
@@ -0,0 +1,212 @@
+//! Decodes every opcode/func pair and checks it against the WRAMP encoding
+//! written out independently of `INSN_TABLE`.
+//!
+//! Reserved encodings (no instruction, decode must fail):
+//! - opcode 0x2, func 0xf
+//! - opcode 0x3, func 0xf
+//! - opcodes 0xd, 0xe and 0xf, any func
+
+use rwobj::instructions::{decode, DecodeError, InsnDescriptor, Operand, Target};
+
+/// Mnemonics by func for the opcode families whose func selects the
+/// operation.
+const ALU_R: [&str; 16] = [
+    "add", "addu", "sub", "subu", "mult", "multu", "div", "divu", "rem", "remu", "sll", "and",
+    "srl", "or", "sra", "xor",
+];
+const ALU_I: [&str; 16] = [
+    "addi", "addui", "subi", "subui", "multi", "multui", "divi", "divui", "remi", "remui", "slli",
+    "andi", "srli", "ori", "srai", "xori",
+];
+const TEST_R: [&str; 16] = [
+    "slt", "sltu", "sgt", "sgtu", "sle", "sleu", "sge", "sgeu", "seq", "sequ", "sne", "sneu",
+    "break", "syscall", "rfe", "",
+];
+const TEST_I: [&str; 16] = [
+    "slti", "sltui", "sgti", "sgtui", "slei", "sleui", "sgei", "sgeui", "seqi", "sequi", "snei",
+    "sneui", "movgs", "movsg", "lhi", "",
+];
+
+/// Register and immediate values chosen so that every field is distinct.
+struct Fields {
+    rd: u32,
+    rs: u32,
+    low: u32,
+}
+
+const FIELD_SETS: [Fields; 2] = [
+    Fields {
+        rd: 3,
+        rs: 5,
+        low: 0x0a57,
+    },
+    Fields {
+        rd: 15,
+        rs: 14,
+        low: 0x8001,
+    },
+];
+
+fn encode(opcode: u32, func: u32, fields: &Fields) -> u32 {
+    (opcode << 28) | (fields.rd << 24) | (fields.rs << 20) | (func << 16) | fields.low
+}
+
+fn sign_extend_20(value: u32) -> i32 {
+    ((value << 12) as i32) >> 12
+}
+
+/// The mnemonic, descriptor and operands `word` must decode to, or `None`
+/// for a reserved encoding.
+fn expected(
+    opcode: u32,
+    func: u32,
+    fields: &Fields,
+) -> Option<(&'static str, InsnDescriptor, Vec<Operand>)> {
+    let rd = Operand::Gpr(fields.rd as u8);
+    let rs = Operand::Gpr(fields.rs as u8);
+    let rt = Operand::Gpr((fields.low & 0xf) as u8);
+    let imm = Operand::Imm(fields.low as u16);
+    let address = (func << 16) | fields.low;
+    let offset = sign_extend_20(address);
+
+    let expected = match (opcode, func) {
+        (0x0, _) => (
+            ALU_R[func as usize],
+            InsnDescriptor::RType,
+            vec![rd, rs, rt],
+        ),
+        (0x1, _) => (
+            ALU_I[func as usize],
+            InsnDescriptor::IType,
+            vec![rd, rs, imm],
+        ),
+        (0x2, 0x0..=0xb) => (
+            TEST_R[func as usize],
+            InsnDescriptor::RType,
+            vec![rd, rs, rt],
+        ),
+        (0x2, 0xc..=0xe) => (TEST_R[func as usize], InsnDescriptor::IType, vec![]),
+        (0x3, 0x0..=0xb) => (
+            TEST_I[func as usize],
+            InsnDescriptor::IType,
+            vec![rd, rs, imm],
+        ),
+        (0x3, 0xc) => (
+            "movgs",
+            InsnDescriptor::IType,
+            vec![Operand::Spr(fields.rd as u8), rs],
+        ),
+        (0x3, 0xd) => (
+            "movsg",
+            InsnDescriptor::IType,
+            vec![rd, Operand::Spr(fields.rs as u8)],
+        ),
+        (0x3, 0xe) => ("lhi", InsnDescriptor::IType, vec![rd, imm]),
+        (0x4, _) => (
+            "j",
+            InsnDescriptor::JType,
+            vec![Operand::Target(Target::Absolute(address))],
+        ),
+        (0x5, _) => ("jr", InsnDescriptor::JType, vec![rs]),
+        (0x6, _) => (
+            "jal",
+            InsnDescriptor::JType,
+            vec![Operand::Target(Target::Absolute(address))],
+        ),
+        (0x7, _) => ("jalr", InsnDescriptor::JType, vec![rs]),
+        (0x8, _) => (
+            "lw",
+            InsnDescriptor::JType,
+            vec![rd, Operand::Offset(offset), rs],
+        ),
+        (0x9, _) => (
+            "sw",
+            InsnDescriptor::JType,
+            vec![rd, Operand::Offset(offset), rs],
+        ),
+        (0xa, _) => (
+            "beqz",
+            InsnDescriptor::JType,
+            vec![rs, Operand::Target(Target::Relative(offset))],
+        ),
+        (0xb, _) => (
+            "bnez",
+            InsnDescriptor::JType,
+            vec![rs, Operand::Target(Target::Relative(offset))],
+        ),
+        (0xc, _) => (
+            "la",
+            InsnDescriptor::JType,
+            vec![rd, Operand::Target(Target::Absolute(address))],
+        ),
+        _ => return None,
+    };
+    Some(expected)
+}
+
+#[test]
+fn every_opcode_func_pair_decodes_as_specified() {
+    for fields in &FIELD_SETS {
+        for opcode in 0..16 {
+            for func in 0..16 {
+                let word = encode(opcode, func, fields);
+                let decoded = decode(word);
+                match expected(opcode, func, fields) {
+                    Some((mnemonic, descriptor, operands)) => {
+                        let insn = decoded.unwrap_or_else(|err| panic!("{:#010x}: {}", word, err));
+                        assert_eq!(insn.insn.mnemonic(), Some(mnemonic), "{:#010x}", word);
+                        assert_eq!(*insn.descriptor(), descriptor, "{:#010x}", word);
+                        assert_eq!(
+                            insn.operands().collect::<Vec<_>>(),
+                            operands,
+                            "{:#010x} ({})",
+                            word,
+                            mnemonic
+                        );
+                    }
+                    None => assert_eq!(
+                        decoded,
+                        Err(DecodeError::UnknownEncoding { word, opcode, func }),
+                        "{:#010x} should be reserved",
+                        word
+                    ),
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn special_encodings_do_not_shadow_their_families() {
+    // lhi, movgs and movsg share opcode 0x3 with the s*i family; break,
+    // syscall and rfe share opcode 0x2 with the s* family.
+    let cases = [
+        (0x3, 0xb, "sneui"),
+        (0x3, 0xc, "movgs"),
+        (0x3, 0xd, "movsg"),
+        (0x3, 0xe, "lhi"),
+        (0x2, 0xb, "sneu"),
+        (0x2, 0xc, "break"),
+        (0x2, 0xd, "syscall"),
+        (0x2, 0xe, "rfe"),
+    ];
+    for (opcode, func, mnemonic) in cases {
+        let word = encode(opcode, func, &FIELD_SETS[0]);
+        assert_eq!(
+            decode(word).unwrap().insn.mnemonic(),
+            Some(mnemonic),
+            "{:#010x}",
+            word
+        );
+    }
+}
+
+#[test]
+fn branch_targets_are_relative_to_the_next_word() {
+    // bnez $1, -2 at address 2 branches back to address 1.
+    let insn = decode(0xb01f_fffe).unwrap();
+    assert_eq!(insn.target_address(2), Some(1));
+    // j 0x12345 is absolute.
+    let insn = decode(0x4001_2345).unwrap();
+    assert_eq!(insn.target_address(7), Some(0x12345));
+}
@@ -0,0 +1,126 @@
+use rwobj::disasm::Disassembler;
+use rwobj::display::{
+    write_disassembly, write_dump, write_header, write_relocs, write_size_row, write_symbols,
+    SizeSummary,
+};
+use rwobj::format::{FormatOptions, Formatter};
+use rwobj::{ObjectFile, SegmentType};
+use std::fs;
+use std::path::Path;
+
+fn hello() -> ObjectFile {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/hello.o");
+    ObjectFile::parse(&fs::read(path).unwrap()).unwrap()
+}
+
+/// What `view` writes for hello.o.
+fn render(view: impl FnOnce(&mut Vec<u8>, &ObjectFile) -> std::io::Result<()>) -> String {
+    let mut out = Vec::new();
+    view(&mut out, &hello()).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn size_totals_do_not_overflow() {
+    let big = SizeSummary {
+        text: 1,
+        bss: u32::MAX.into(),
+        relocs: u32::MAX.into(),
+        ..SizeSummary::default()
+    };
+    let mut totals = SizeSummary::default();
+    totals.add(&big);
+    totals.add(&big);
+    assert_eq!(totals.bss, 2 * u32::MAX as u64);
+    assert_eq!(totals.total(), 2 + 2 * u32::MAX as u64);
+
+    let mut out = Vec::new();
+    write_size_row(&mut out, &totals, "(TOTALS)").unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "       2        0 8589934590 8589934592 8589934590        0 (TOTALS)\n"
+    );
+}
+
+#[test]
+fn dump_marks_relocated_words_and_shows_characters() {
+    assert_eq!(
+        render(|out, object| write_dump(out, object, SegmentType::Text)),
+        "Dump of TEXT segment (8 words):\n\
+         0x00000:  1ee30002  9fe00001  c2000000* 92e00000   |....|  0x00002=DATA_LABEL_REF\n\
+         0x00004:  60000000* 8fe00001  1ee10002  50f00000   |....|  0x00004=printf\n"
+    );
+    assert_eq!(
+        render(|out, object| write_dump(out, object, SegmentType::Data)),
+        "Dump of DATA segment (6 words):\n\
+         0x00008:  00000068  00000069  0000000a  00000000   |hi..|\n\
+         0x0000c:  00000000  00000000*                      |..|  0x0000d=DATA_LABEL_REF\n"
+    );
+}
+
+#[test]
+fn header_view_lists_every_field() {
+    assert_eq!(
+        render(write_header),
+        "Header:
+  Magic:              0xdaa1
+  Text size:          8 words
+  Data size:          6 words
+  Bss size:           4 words
+  Relocations:        6
+  Symbol table size:  24 bytes
+"
+    );
+}
+
+#[test]
+fn relocs_view_lists_every_entry() {
+    assert_eq!(
+        render(write_relocs),
+        "Relocations (6 entries):
+  Index  Address  Type            Segment  Symbol
+      0  0x00000  GLOBAL_TEXT     -        main
+      1  0x00002  DATA_LABEL_REF  TEXT     -
+      2  0x00004  EXTERNAL_REF    TEXT     printf
+      3  0x00004  GLOBAL_DATA     -        counter
+      4  0x00005  DATA_LABEL_REF  DATA     -
+      5  0x00000  GLOBAL_BSS      -        buf
+"
+    );
+}
+
+#[test]
+fn symbols_view_marks_externals_undefined() {
+    assert_eq!(
+        render(write_symbols),
+        "Symbols (4 entries):
+  Name     Segment  Address  Global
+  main     TEXT     0x00000  yes
+  printf   UNDEF    -        no
+  counter  DATA     0x00004  yes
+  buf      BSS      0x00000  yes
+"
+    );
+}
+
+#[test]
+fn calls_to_externals_are_flagged_unresolved() {
+    let object = hello();
+    let formatter = Formatter::new(FormatOptions {
+        show_raw: false,
+        ..FormatOptions::default()
+    });
+    let disassembler = Disassembler::new(&object, formatter.clone());
+    assert_eq!(
+        disassembler.instruction_line(4).unwrap(),
+        "        jal      printf  # unresolved external"
+    );
+    assert!(!disassembler.instruction_line(5).unwrap().contains('#'));
+
+    let listing = render(|out, object| write_disassembly(out, object, &formatter));
+    let flagged: Vec<&str> = listing
+        .lines()
+        .filter(|line| line.contains("# unresolved external"))
+        .collect();
+    assert_eq!(flagged, ["        jal      printf  # unresolved external"]);
+}
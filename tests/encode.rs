@@ -0,0 +1,172 @@
+use proptest::prelude::*;
+use rwobj::instructions::{
+    decode, encode, immediate, EncodeError, Field, InsnDescriptor, InsnType, Instruction, Operand,
+    OperandKind, Target, INSN_TABLE,
+};
+
+/// Every `INSN_TABLE` entry that is a machine instruction.
+fn machine_instructions() -> Vec<&'static InsnType> {
+    INSN_TABLE
+        .iter()
+        .filter(|insn| {
+            matches!(
+                insn.type_descriptor(),
+                InsnDescriptor::RType | InsnDescriptor::IType | InsnDescriptor::JType
+            )
+        })
+        .collect()
+}
+
+/// Any valid value for an operand of the given kind.
+fn operand(kind: OperandKind) -> BoxedStrategy<Operand> {
+    match kind {
+        OperandKind::Gpr(_) => (0u8..16).prop_map(Operand::Gpr).boxed(),
+        OperandKind::Spr(_) => (0u8..16).prop_map(Operand::Spr).boxed(),
+        OperandKind::Imm16 => any::<u16>().prop_map(Operand::Imm).boxed(),
+        OperandKind::Offset20 => (-0x80000i32..0x80000).prop_map(Operand::Offset).boxed(),
+        OperandKind::Relative20 => (-0x80000i32..0x80000)
+            .prop_map(|offset| Operand::Target(Target::Relative(offset)))
+            .boxed(),
+        OperandKind::Absolute20 => (0u32..0x100000)
+            .prop_map(|address| Operand::Target(Target::Absolute(address)))
+            .boxed(),
+    }
+}
+
+fn instruction() -> impl Strategy<Value = Instruction<'static>> {
+    proptest::sample::select(machine_instructions()).prop_flat_map(|insn| {
+        let operands: Vec<_> = insn.operands().iter().copied().map(operand).collect();
+        operands.prop_map(move |operands| Instruction::new(insn, &operands))
+    })
+}
+
+proptest! {
+    #[test]
+    fn decode_inverts_encode(insn in instruction()) {
+        let word = encode(&insn).unwrap();
+        prop_assert_eq!(decode(word), Ok(insn));
+    }
+
+    #[test]
+    fn encode_inverts_decode_up_to_ignored_bits(word in any::<u32>()) {
+        if let Ok(insn) = decode(word) {
+            let encoded = encode(&insn).unwrap();
+            prop_assert_eq!(decode(encoded), Ok(insn));
+            prop_assert_eq!(encoded & !word, 0, "{:#010x} -> {:#010x}", word, encoded);
+        }
+    }
+}
+
+fn insn(mnemonic: &str) -> &'static InsnType {
+    INSN_TABLE
+        .iter()
+        .find(|insn| insn.mnemonic() == Some(mnemonic))
+        .unwrap()
+}
+
+#[test]
+fn encodes_known_words() {
+    let cases = [
+        (
+            Instruction::new(
+                insn("addui"),
+                &[Operand::Gpr(14), Operand::Gpr(14), Operand::Imm(1)],
+            ),
+            0x1ee1_0001,
+        ),
+        (
+            Instruction::new(
+                insn("sw"),
+                &[Operand::Gpr(15), Operand::Offset(-1), Operand::Gpr(14)],
+            ),
+            0x9fef_ffff,
+        ),
+        (
+            Instruction::new(
+                insn("bnez"),
+                &[Operand::Gpr(1), Operand::Target(Target::Relative(-2))],
+            ),
+            0xb01f_fffe,
+        ),
+        (
+            Instruction::new(insn("movsg"), &[Operand::Gpr(2), Operand::Spr(5)]),
+            0x325d_0000,
+        ),
+        (Instruction::new(insn("syscall"), &[]), 0x200d_0000),
+    ];
+    for (insn, word) in cases {
+        assert_eq!(encode(&insn), Ok(word), "{:?}", insn.insn.mnemonic());
+    }
+}
+
+#[test]
+fn rejects_out_of_range_fields() {
+    let sw = Instruction::new(
+        insn("sw"),
+        &[Operand::Gpr(1), Operand::Offset(0x80000), Operand::Gpr(2)],
+    );
+    assert_eq!(
+        encode(&sw),
+        Err(EncodeError::OffsetOutOfRange {
+            index: 1,
+            offset: 0x80000
+        })
+    );
+
+    let j = Instruction::new(insn("j"), &[Operand::Target(Target::Absolute(0x100000))]);
+    assert_eq!(
+        encode(&j),
+        Err(EncodeError::TargetOutOfRange {
+            index: 0,
+            target: 0x100000
+        })
+    );
+
+    let add = Instruction::new(
+        insn("add"),
+        &[Operand::Gpr(1), Operand::Gpr(16), Operand::Gpr(3)],
+    );
+    assert_eq!(
+        encode(&add),
+        Err(EncodeError::RegisterOutOfRange {
+            index: 1,
+            register: 16
+        })
+    );
+
+    assert_eq!(immediate(-0x8000), Ok(0x8000));
+    assert_eq!(immediate(0xffff), Ok(0xffff));
+    assert_eq!(
+        immediate(0x10000),
+        Err(EncodeError::ImmediateOutOfRange { value: 0x10000 })
+    );
+    assert_eq!(
+        immediate(-0x8001),
+        Err(EncodeError::ImmediateOutOfRange { value: -0x8001 })
+    );
+}
+
+#[test]
+fn rejects_operands_that_do_not_fit_the_operand_string() {
+    let movgs = Instruction::new(insn("movgs"), &[Operand::Gpr(1), Operand::Gpr(2)]);
+    assert_eq!(
+        encode(&movgs),
+        Err(EncodeError::OperandMismatch {
+            index: 0,
+            expected: OperandKind::Spr(Field::Rd),
+            found: Operand::Gpr(1)
+        })
+    );
+
+    let jr = Instruction::new(insn("jr"), &[]);
+    assert_eq!(
+        encode(&jr),
+        Err(EncodeError::WrongOperandCount {
+            expected: 1,
+            found: 0
+        })
+    );
+
+    let word = Instruction::new(insn(".word"), &[]);
+    assert_eq!(encode(&word), Err(EncodeError::NotAnInstruction));
+}
@@ -0,0 +1,67 @@
+use rwobj::format::{FormatOptions, Formatter, Radix};
+
+const ADDI: u32 = 0x1ee0_fffe; // addi $sp, $sp, -2
+const LW: u32 = 0x83e0_0004; // lw $3, 4($sp)
+const J: u32 = 0x4000_0005; // j 5
+
+/// `word` at address 0x10 under the default options with `change`
+/// applied.
+fn format(change: impl FnOnce(&mut FormatOptions), word: u32, symbol: Option<&str>) -> String {
+    let mut options = FormatOptions::default();
+    change(&mut options);
+    Formatter::new(options)
+        .format_word(0x10, word, symbol)
+        .unwrap()
+}
+
+#[test]
+fn defaults_show_raw_hex_columns() {
+    assert_eq!(
+        format(|_| {}, ADDI, None),
+        "0x00010:  1ee0fffe  addi     $sp, $sp, 0xfffe"
+    );
+    assert_eq!(
+        format(|options| options.show_raw = false, LW, None),
+        "lw       $3, 0x4($sp)"
+    );
+}
+
+#[test]
+fn decimal_radix_sign_extends_immediates() {
+    let decimal = |options: &mut FormatOptions| {
+        options.radix = Radix::Decimal;
+        options.show_raw = false;
+    };
+    assert_eq!(format(decimal, ADDI, None), "addi     $sp, $sp, -2");
+    assert_eq!(format(decimal, J, None), "j        5");
+    assert_eq!(
+        format(|options| options.radix = Radix::Decimal, LW, None),
+        "     16:  83e00004  lw       $3, 4($sp)"
+    );
+}
+
+#[test]
+fn symbols_replace_targets_only_when_resolved() {
+    let plain = |options: &mut FormatOptions| options.show_raw = false;
+    assert_eq!(format(plain, J, Some("main")), "j        main");
+    let unresolved = |options: &mut FormatOptions| {
+        options.show_raw = false;
+        options.resolve_symbols = false;
+    };
+    assert_eq!(format(unresolved, J, Some("main")), "j        0x00005");
+}
+
+#[test]
+fn separator_and_mnemonic_width_are_configurable() {
+    let compact = |options: &mut FormatOptions| {
+        options.show_raw = false;
+        options.operand_separator = ",";
+        options.mnemonic_width = 0;
+    };
+    assert_eq!(format(compact, ADDI, None), "addi $sp,$sp,0xfffe");
+    let wide = |options: &mut FormatOptions| {
+        options.show_raw = false;
+        options.mnemonic_width = 10;
+    };
+    assert_eq!(format(wide, LW, None), "lw         $3, 0x4($sp)");
+}
@@ -0,0 +1,148 @@
+use rwobj::format::{FormatOptions, Formatter};
+use rwobj::instructions::{decode, InsnDescriptor};
+use rwobj::isa::{Isa, IsaError};
+use std::sync::Arc;
+
+const LAB_VARIANT: &str = r#"
+name = "lab"
+
+[registers]
+gpr = { 12 = "$fp" }
+spr = { 14 = "$cop0", 15 = "$cop1" }
+
+[[instruction]]
+mnemonic = "cop"
+format = "I"
+opcode = 0xd
+func = 0x2
+operands = ["gpr(rd)", "gpr(rs)", "imm16"]
+tags = ["coprocessor", "zero_extend"]
+"#;
+
+fn formatter(isa: Isa) -> Formatter {
+    Formatter::with_isa(
+        FormatOptions {
+            show_raw: false,
+            ..FormatOptions::default()
+        },
+        Arc::new(isa),
+    )
+}
+
+#[test]
+fn variant_renames_registers_and_adds_instructions() {
+    let isa = Isa::builtin().with_variant("ignored", LAB_VARIANT).unwrap();
+    assert_eq!(isa.name(), "lab");
+    let formatter = formatter(isa);
+
+    let line = |word| formatter.format_word(0, word, None).unwrap();
+    assert_eq!(line(0x0c10_0003), "add      $fp, $1, $3");
+    assert_eq!(line(0x31ed_0000), "movsg    $1, $cop0");
+    assert_eq!(line(0xd122_0010), "cop      $1, $2, 0x0010");
+    assert!(formatter.isa().find("cop").unwrap().has_tag("coprocessor"));
+
+    // The built-in tables are untouched.
+    assert!(decode(0xd122_0010).is_err());
+    assert_eq!(Isa::builtin().gpr_name(12), "$12");
+}
+
+#[test]
+fn variant_entries_replace_builtins_with_the_same_mnemonic() {
+    let variant = r#"
+        [[instruction]]
+        mnemonic = "break"
+        format = "I"
+        opcode = 0x2
+        func = 0xf
+        operands = []
+    "#;
+    let isa = Isa::builtin().with_variant("trap", variant).unwrap();
+    assert_eq!(isa.name(), "trap");
+    assert_eq!(
+        isa.table()
+            .iter()
+            .filter(|entry| entry.mnemonic() == Some("break"))
+            .count(),
+        1
+    );
+    assert_eq!(
+        isa.decode(0x200f_0000).unwrap().insn.mnemonic(),
+        Some("break")
+    );
+    assert!(isa.decode(0x200c_0000).is_err());
+}
+
+#[test]
+fn variant_entries_win_overlapping_encodings() {
+    let variant = r#"
+        [[instruction]]
+        mnemonic = "fadd"
+        format = "R"
+        opcode = 0x0
+        func = 0x0
+        operands = ["gpr(rd)", "gpr(rs)", "gpr(rt)"]
+    "#;
+    let isa = Isa::builtin().with_variant("float", variant).unwrap();
+    let insn = isa.decode(0x0120_0004).unwrap();
+    assert_eq!(insn.insn.mnemonic(), Some("fadd"));
+    assert_eq!(*insn.descriptor(), InsnDescriptor::RType);
+    assert!(isa.find("add").is_some());
+}
+
+#[test]
+fn invalid_variants_are_rejected() {
+    let cases = [
+        (
+            r#"
+            [[instruction]]
+            mnemonic = "bad"
+            format = "R"
+            opcode = 0xd
+            func = 0x0
+            operands = ["gpr(rd)", "imm16"]
+            "#,
+            "operands do not fit",
+        ),
+        (
+            r#"
+            [[instruction]]
+            mnemonic = "bad"
+            format = "I"
+            opcode = 0xd
+            func = 0x0
+            operands = ["gpr(rq)"]
+            "#,
+            "unknown operand",
+        ),
+        (
+            r#"
+            [[instruction]]
+            mnemonic = "bad"
+            format = "I"
+            opcode = 0x10
+            func = 0x0
+            operands = []
+            "#,
+            "does not fit in 4 bits",
+        ),
+        ("[registers]\ngpr = { 16 = \"$x\" }", "no register"),
+        ("[registers]\nspr = [\"$a\"]", "not 16"),
+    ];
+    for (text, expected) in cases {
+        match Isa::builtin().with_variant("bad", text) {
+            Err(IsaError::Invalid(reason)) => {
+                assert!(
+                    reason.contains(expected),
+                    "{:?} lacks {:?}",
+                    reason,
+                    expected
+                )
+            }
+            other => panic!(
+                "{:?} was not rejected: {:?}",
+                text,
+                other.map(|isa| isa.name().to_string())
+            ),
+        }
+    }
+}
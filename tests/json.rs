@@ -0,0 +1,62 @@
+use rwobj::isa::Isa;
+use rwobj::json::instructions_to_json;
+use rwobj::{ObjectFile, SymbolTable};
+use serde_json::json;
+
+#[test]
+fn instructions_carry_typed_operands() {
+    let text = vec![
+        0x1ee0_fffe, // addi $sp, $sp, -2
+        0x83e0_0004, // lw $3, 4($sp)
+        0xa03f_fffd, // beqz $3, 0
+        0x4000_0005, // j 5
+        0xf0f0_0000, // no such instruction
+    ];
+    let object = ObjectFile::from_parts(text, Vec::new(), 0, Vec::new(), SymbolTable::default());
+    let isa = Isa::builtin();
+    let gpr = |number: u8| json!({ "kind": "gpr", "number": number, "name": isa.gpr_name(number) });
+    assert_eq!(
+        instructions_to_json(&object, &isa),
+        json!([
+            {
+                "address": 0,
+                "word": 0x1ee0_fffe_u32,
+                "unknown": false,
+                "mnemonic": "addi",
+                "operands": [gpr(14), gpr(14), { "kind": "immediate", "value": -2 }],
+            },
+            {
+                "address": 1,
+                "word": 0x83e0_0004_u32,
+                "unknown": false,
+                "mnemonic": "lw",
+                "operands": [gpr(3), { "kind": "offset", "value": 4 }, gpr(14)],
+            },
+            {
+                "address": 2,
+                "word": 0xa03f_fffd_u32,
+                "unknown": false,
+                "mnemonic": "beqz",
+                "operands": [
+                    gpr(3),
+                    { "kind": "target", "relative": true, "offset": -3, "address": 0 },
+                ],
+            },
+            {
+                "address": 3,
+                "word": 0x4000_0005_u32,
+                "unknown": false,
+                "mnemonic": "j",
+                "operands": [{ "kind": "target", "relative": false, "address": 5 }],
+            },
+            {
+                "address": 4,
+                "word": 0xf0f0_0000_u32,
+                "unknown": true,
+                "mnemonic": null,
+                "opcode": 0xf,
+                "func": 0,
+            },
+        ])
+    );
+}
@@ -0,0 +1,182 @@
+use rwobj::display::write_segments;
+use rwobj::{ObjError, ObjectFile, SegmentType};
+use std::fs;
+use std::path::Path;
+
+/// hello.o: 8 text words at 0x18, 6 data words at 0x38, 6 relocation
+/// entries at 0x50 and a 24-byte name table at 0x8c.
+fn hello() -> Vec<u8> {
+    fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/hello.o")).unwrap()
+}
+
+/// Parses hello.o after `corrupt` has edited its bytes, which must fail.
+fn parse_corrupted(corrupt: impl FnOnce(&mut Vec<u8>)) -> ObjError {
+    let mut bytes = hello();
+    corrupt(&mut bytes);
+    let err = ObjectFile::parse(&bytes).unwrap_err();
+    assert!(err.is_corrupt(), "{:?}", err);
+    err
+}
+
+/// A header followed by `text` words and nothing else.
+fn image(text: &[u32], bss_size: u32) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for field in [0xdaa1, text.len() as u32, 0, bss_size, 0, 0] {
+        bytes.extend(field.to_le_bytes());
+    }
+    for word in text {
+        bytes.extend(word.to_le_bytes());
+    }
+    bytes
+}
+
+#[test]
+fn segments_beyond_the_address_space_are_rejected() {
+    let err = ObjectFile::parse(&image(&[0], 0xffff_ffff)).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            ObjError::SegmentTooLarge {
+                section: "bss segment",
+                offset: 12,
+                size: 0xffff_ffff,
+            }
+        ),
+        "{:?}",
+        err
+    );
+
+    let object = ObjectFile::parse(&image(&[0], 0x10_0000)).unwrap();
+    let mut out = Vec::new();
+    write_segments(&mut out, &object).unwrap();
+    assert!(String::from_utf8(out)
+        .unwrap()
+        .contains("0x00001  0x100001  1048576"));
+}
+
+#[test]
+fn corrupt_objects_report_what_is_wrong_and_where() {
+    ObjectFile::parse(&hello()).unwrap();
+
+    let err = parse_corrupted(|bytes| bytes.truncate(10));
+    assert!(
+        matches!(err, ObjError::TruncatedHeader { offset: 10 }),
+        "{:?}",
+        err
+    );
+
+    let err = parse_corrupted(|bytes| bytes[0] = 0);
+    assert!(
+        matches!(
+            err,
+            ObjError::BadMagic {
+                offset: 0,
+                found: 0xda00
+            }
+        ),
+        "{:?}",
+        err
+    );
+
+    let err = parse_corrupted(|bytes| bytes.truncate(100));
+    assert!(
+        matches!(
+            err,
+            ObjError::SegmentPastEof {
+                section: "relocation table",
+                offset: 0x50,
+                size: 60,
+                file_len: 100,
+            }
+        ),
+        "{:?}",
+        err
+    );
+
+    let err = parse_corrupted(|bytes| bytes[0x50 + 8] = 9);
+    assert!(
+        matches!(
+            err,
+            ObjError::BadRefType {
+                index: 0,
+                offset: 0x58,
+                code: 9
+            }
+        ),
+        "{:?}",
+        err
+    );
+
+    let err = parse_corrupted(|bytes| bytes[0x5a + 9] = 7);
+    assert!(
+        matches!(
+            err,
+            ObjError::BadSegType {
+                index: 1,
+                offset: 0x63,
+                code: 7
+            }
+        ),
+        "{:?}",
+        err
+    );
+
+    // Entry 1 is a data label reference patching the 8-word text segment.
+    let err = parse_corrupted(|bytes| bytes[0x5a] = 8);
+    assert!(
+        matches!(
+            err,
+            ObjError::RelocOutOfRange {
+                index: 1,
+                offset: 0x5a,
+                address: 8,
+                segment: SegmentType::Text,
+                size: 8,
+            }
+        ),
+        "{:?}",
+        err
+    );
+
+    // Entry 2 is an external reference to `printf`.
+    let err = parse_corrupted(|bytes| bytes[0x64 + 4] = 24);
+    assert!(
+        matches!(
+            err,
+            ObjError::BadSymbolPointer {
+                index: 2,
+                offset: 0x68,
+                ptr: 24,
+                table_size: 24,
+            }
+        ),
+        "{:?}",
+        err
+    );
+
+    let err = parse_corrupted(|bytes| *bytes.last_mut().unwrap() = b'x');
+    assert!(
+        matches!(
+            err,
+            ObjError::BadSymbolTable {
+                offset: 0xa0,
+                reason: "missing NUL terminator",
+            }
+        ),
+        "{:?}",
+        err
+    );
+
+    let err = parse_corrupted(|bytes| bytes.extend([1, 2, 3]));
+    assert!(
+        matches!(
+            err,
+            ObjError::TrailingGarbage {
+                offset: 0xa4,
+                len: 3
+            }
+        ),
+        "{:?}",
+        err
+    );
+}
@@ -0,0 +1,65 @@
+use rwobj::{ObjectFile, ReferenceType, RelocEntry, SegmentType, SymbolTable};
+use std::fs;
+use std::path::Path;
+
+fn fixtures() -> Vec<(String, Vec<u8>)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut fixtures: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "o"))
+        .map(|path| (path.display().to_string(), fs::read(&path).unwrap()))
+        .collect();
+    fixtures.sort();
+    fixtures
+}
+
+#[test]
+fn fixtures_round_trip_byte_for_byte() {
+    let fixtures = fixtures();
+    assert!(!fixtures.is_empty());
+    for (name, bytes) in fixtures {
+        let object = ObjectFile::parse(&bytes).unwrap_or_else(|err| panic!("{}: {}", name, err));
+        assert_eq!(object.to_bytes(), bytes, "{} did not round-trip", name);
+    }
+}
+
+#[test]
+fn built_object_parses_back() {
+    let mut symbols = SymbolTable::default();
+    let main = symbols.intern("main");
+    let printf = symbols.intern("printf");
+    assert_eq!(symbols.intern("main"), main);
+
+    let reloc_entries = vec![
+        RelocEntry {
+            address: 0,
+            symbol_ptr: main,
+            ref_type: ReferenceType::GlobalText,
+            seg_type: None,
+            symbol_name: None,
+        },
+        RelocEntry {
+            address: 1,
+            symbol_ptr: printf,
+            ref_type: ReferenceType::ExternalRef,
+            seg_type: Some(SegmentType::Text),
+            symbol_name: None,
+        },
+    ];
+    let object = ObjectFile::from_parts(
+        vec![0x1EE30002, 0x60000000],
+        vec![0x68, 0x69, 0],
+        2,
+        reloc_entries,
+        symbols,
+    );
+
+    let parsed = ObjectFile::parse(&object.to_bytes()).unwrap();
+    assert_eq!(parsed, object);
+    assert_eq!(
+        parsed.reloc_entries[1].symbol_name.as_deref(),
+        Some("printf")
+    );
+    assert!(parsed.check().is_empty());
+}